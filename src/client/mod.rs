@@ -1,12 +1,15 @@
 use anyhow::Result;
 use log::debug;
 
-use crate::{events::PayloadDuplex, gateway::Shard, http::Http, models::{Gateway, Guild, UnavailableGuild, message::Message}, store::memory::MemoryStore};
+use crate::{gateway::ShardManager, http::Http, models::{BotGateway, Channel, Guild, Member, UnavailableGuild, message::Message}, store::memory::MemoryStore};
 
 mod context;
 mod run;
 
-pub use self::{context::Context, run::Runner};
+pub use self::{
+    context::Context,
+    run::{Runner, RunnerHandle},
+};
 
 pub struct Client {
     token: String,
@@ -34,20 +37,23 @@ impl Client {
     }
 
     pub async fn default_runner(&self) -> Result<Runner> {
-        let gateway = Gateway::get(&self.context()).await?;
+        let gateway = BotGateway::get(&self.context()).await?;
 
         debug!("[Client] Using gateway {:?}", gateway);
 
-        let shards: Vec<Box<dyn PayloadDuplex>> =
-            vec![Box::new(Shard::default_with(gateway, self.token.clone()))];
+        let shards = ShardManager::default_with(gateway, self.token.clone())
+            .spawn()
+            .await?;
 
         let mut runner = Runner::new();
 
         runner
-            .add_payload_duplexes(shards)
+            .add_shards(shards)
             .register_store(MemoryStore::<UnavailableGuild>::new())
             .register_store(MemoryStore::<Guild>::new())
-            .register_store(MemoryStore::<Message>::new());
+            .register_store(MemoryStore::<Channel>::new())
+            .register_store(MemoryStore::<Message>::new())
+            .register_store(MemoryStore::<Member>::new());
 
         Ok(runner)
     }
@@ -1,11 +1,14 @@
 use async_trait::async_trait;
+use futures::{future::try_join_all, pin_mut, StreamExt};
 use futures_async_stream::try_stream;
+use log::warn;
 use streamunordered::{StreamUnordered, StreamYield};
 use type_map::concurrent::TypeMap;
 
 use crate::{
-    events::{Event, PayloadDuplex, StoreUpdate},
-    models::Resource,
+    events::{payload::UpdatePresence, Event, PayloadDuplex, StoreUpdate},
+    gateway::{GatewayConnector, GatewayError, SessionInfo, Shard, ShardHandle},
+    models::{GuildId, Member, Resource},
     store::{multiplex::MultiplexedStore, Store},
 };
 
@@ -54,11 +57,82 @@ where
     async fn remove_one(&self, id: &R::Id) -> Option<R> {
         self.store().remove_one(id).await
     }
+
+    async fn remove_where(&self, predicate: &(dyn Fn(&R) -> bool + Send + Sync)) -> Vec<R> {
+        self.store().remove_where(predicate).await
+    }
+}
+
+/// A handle to the shards of a [`Runner`], through which payloads can be sent
+/// while it is running.
+#[derive(Clone, Debug, Default)]
+pub struct RunnerHandle {
+    shards: Vec<ShardHandle>,
+}
+
+impl RunnerHandle {
+    pub fn shards(&self) -> &[ShardHandle] {
+        &self.shards
+    }
+
+    /// The handle to the shard with the given ID. Unsharded shards have ID 0.
+    pub fn shard(&self, id: u64) -> Option<&ShardHandle> {
+        self.shards
+            .iter()
+            .find(|shard| shard.shard_info().map_or(0, |info| info.id) == id)
+    }
+
+    /// The handle to the shard which receives events for the given guild.
+    ///
+    /// The guild's shard is determined by the total shard count, which may be
+    /// larger than the number of shards run by this runner.
+    pub fn shard_for_guild(&self, guild_id: GuildId) -> Option<&ShardHandle> {
+        let shard_count = self
+            .shards
+            .first()?
+            .shard_info()
+            .map_or(1, |info| info.count);
+
+        self.shard(guild_id.shard_id(shard_count))
+    }
+
+    /// Requests members of a guild through the shard it belongs to. See
+    /// [`ShardHandle::request_members`].
+    pub async fn request_members(
+        &self,
+        guild_id: GuildId,
+        query: impl Into<String>,
+        limit: u64,
+    ) -> anyhow::Result<Vec<Member>> {
+        self.shard_for_guild(guild_id)
+            .ok_or_else(|| anyhow::anyhow!("No shard for guild {:?}", guild_id))?
+            .request_members(guild_id, query, limit)
+            .await
+    }
+
+    /// Shuts down all shards, after which [`Runner::run`] ends once the events
+    /// that were already received have been yielded. See
+    /// [`Shard::shutdown`].
+    ///
+    /// The returned sessions are in the same order as [`shards`](Self::shards).
+    pub async fn shutdown(&self, is_resumable: bool) -> anyhow::Result<Vec<Option<SessionInfo>>> {
+        try_join_all(self.shards.iter().map(|shard| shard.shutdown(is_resumable))).await
+    }
+
+    /// Updates the bot's presence on all shards.
+    pub fn update_presence(&self, presence: UpdatePresence) -> anyhow::Result<()> {
+        for shard in &self.shards {
+            shard.update_presence(presence.clone())?;
+        }
+
+        Ok(())
+    }
 }
 
 pub struct Runner {
     pub(crate) payload_duplexes: Vec<Box<dyn PayloadDuplex>>,
     pub(crate) stores: StoreCollection,
+    handle: RunnerHandle,
 }
 
 impl Runner {
@@ -66,7 +140,28 @@ impl Runner {
         Self {
             payload_duplexes: Default::default(),
             stores: Default::default(),
+            handle: Default::default(),
+        }
+    }
+
+    /// A handle through which payloads can be sent to the runner's shards.
+    ///
+    /// Only shards that were added using [`add_shards`](Self::add_shards)
+    /// before calling this are reachable through the handle.
+    pub fn handle(&self) -> RunnerHandle {
+        self.handle.clone()
+    }
+
+    pub fn add_shards<C>(&mut self, shards: impl IntoIterator<Item = Shard<C>>) -> &mut Self
+    where
+        C: 'static + GatewayConnector + Send + Sync,
+    {
+        for shard in shards {
+            self.handle.shards.push(shard.handle());
+            self.payload_duplexes.push(Box::new(shard));
         }
+
+        self
     }
 
     pub fn add_payload_duplexes(
@@ -112,19 +207,122 @@ impl Runner {
             })
             .collect::<StreamUnordered<_>>();
 
-        #[for_await]
-        for (result, _token) in payload_stream {
+        pin_mut!(payload_stream);
+
+        // The stream ends once all shards have been shut down.
+        while let Some((result, _token)) = payload_stream.next().await {
             match result {
                 StreamYield::Item(payload) => {
-                    let mut payload = payload?;
+                    // Shards reconnect on their own after recoverable errors, and a
+                    // payload which can't be decoded only affects itself, so only
+                    // unrecoverable gateway errors should end the stream.
+                    let mut payload = match payload {
+                        Ok(payload) => payload,
+                        Err(err) => match err.downcast_ref::<GatewayError>() {
+                            Some(gateway_err) if !gateway_err.is_recoverable() => Err(err)?,
+                            Some(gateway_err) => {
+                                warn!("[Runner] Recoverable gateway error: {}", gateway_err);
+                                continue;
+                            }
+                            None => {
+                                warn!("[Runner] Skipping payload: {:?}", err);
+                                continue;
+                            }
+                        },
+                    };
 
                     #[for_await]
                     for event in payload.update(&self.stores) {
                         yield event?;
                     }
                 }
-                StreamYield::Finished(stream) => stream.keep(),
+                StreamYield::Finished(stream) => stream.remove(payload_stream.as_mut()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::channel::mpsc;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        events::payload::ShardInfo,
+        gateway::{
+            test_support::{self, FakeGateway, ScriptedConnection, Step},
+            Frame, GatewayCloseCode, GatewayCompression,
+        },
+    };
+
+    #[test]
+    fn finds_shard_for_guild_by_total_shard_count() {
+        // This runner only runs shards 2 and 3 out of 4.
+        let shards = (2..4)
+            .map(|id| {
+                let info = ShardInfo { id, count: 4 };
+                ShardHandle::new(Some(info), mpsc::unbounded().0, mpsc::unbounded().0)
+            })
+            .collect();
+        let handle = RunnerHandle { shards };
+
+        let guild_id: GuildId =
+            serde_json::from_value(json!({ "id": (3u64 << 22).to_string() })).unwrap();
+        let shard = handle.shard_for_guild(guild_id).unwrap();
+
+        assert_eq!(shard.shard_info().unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn skips_undecodable_payloads() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway.script(
+            ScriptedConnection::new()
+                .hello(Duration::from_secs(40))
+                .expect_identify()
+                .ready("session", &gateway.url())
+                .step(Step::Frame(Frame::Text("not a payload".to_owned())))
+                .dispatch(
+                    "GUILD_CREATE",
+                    test_support::guild_create(1, "Guild", Vec::new()),
+                )
+                .close(4004, "Authentication failed"),
+        );
+
+        let shard = Shard::new(
+            gateway.gateway(),
+            "token".to_owned(),
+            Default::default(),
+            GatewayCompression::None,
+            connector,
+        );
+
+        let mut runner = Runner::new();
+        runner.add_shards(Some(shard));
+
+        let events = runner.run();
+        pin_mut!(events);
+
+        let mut received_guild = false;
+        let err = loop {
+            match events.next().await.unwrap() {
+                Ok(Event::GuildAvailable { .. }) | Ok(Event::GuildJoined { .. }) => {
+                    received_guild = true
+                }
+                Ok(_) => {}
+                Err(err) => break err,
             }
+        };
+
+        assert!(received_guild);
+
+        if let Some(GatewayError::Closed { code, .. }) = err.downcast_ref() {
+            assert_eq!(*code, GatewayCloseCode::AuthenticationFailed);
+        } else {
+            panic!("Expected the shard to be closed, got {:?}", err);
         }
     }
 }
@@ -11,6 +11,8 @@ pub trait PayloadDelegate {
         match payload {
             Payload::Dispatch(data) => self.dispatch(data).await,
             Payload::Heartbeat { data } => self.heartbeat_req(data).await,
+            Payload::Reconnect => self.reconnect_req().await,
+            Payload::InvalidSession { data } => self.invalid_session(data).await,
             Payload::Hello { data } => self.hello(data).await,
             Payload::HeartbeatAck => self.heartbeat_ack().await,
 
@@ -32,6 +34,14 @@ pub trait PayloadDelegate {
         Ok(())
     }
 
+    async fn reconnect_req(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn invalid_session(&mut self, data: &InvalidSession) -> Result<()> {
+        Ok(())
+    }
+
     async fn hello(&mut self, data: &Hello) -> Result<()> {
         Ok(())
     }
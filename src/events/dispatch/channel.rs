@@ -1 +1,116 @@
+use chrono::{DateTime, Utc};
+use futures_async_stream::try_stream;
 use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, StoreUpdate},
+    models::{Channel, ChannelId, GuildId, ResourceId, Snowflake},
+    store::Store,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ChannelCreate {
+    #[serde(flatten)]
+    pub channel: Channel,
+}
+
+impl<S> StoreUpdate<S> for ChannelCreate
+where
+    S: Store<Channel>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        store.insert_one(&self.channel).await;
+
+        let channel = self.channel.clone();
+        yield Event::ChannelCreated { channel };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ChannelUpdate {
+    #[serde(flatten)]
+    pub channel: Channel,
+}
+
+impl<S> StoreUpdate<S> for ChannelUpdate
+where
+    S: Store<Channel>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let before = store.insert_one(&self.channel).await;
+
+        let after = self.channel.clone();
+        yield Event::ChannelUpdated { before, after };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ChannelDelete {
+    #[serde(flatten)]
+    pub channel: Channel,
+}
+
+impl<S> StoreUpdate<S> for ChannelDelete
+where
+    S: Store<Channel>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let channel = Store::<Channel>::remove_one(store, self.channel.id())
+            .await
+            .unwrap_or_else(|| self.channel.clone());
+
+        yield Event::ChannelDeleted { channel };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ChannelPinsUpdate {
+    #[serde(default)]
+    guild_id: Option<Snowflake>,
+
+    channel_id: Snowflake,
+
+    /// Not sent if the last pinned message was unpinned.
+    #[serde(rename = "last_pin_timestamp", default)]
+    pub last_pin_at: Option<DateTime<Utc>>,
+}
+
+impl ChannelPinsUpdate {
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId { id })
+    }
+
+    pub fn channel_id(&self) -> ChannelId {
+        ChannelId {
+            id: self.channel_id,
+        }
+    }
+}
+
+impl<S> StoreUpdate<S> for ChannelPinsUpdate
+where
+    S: Store<Channel>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let channel_id = self.channel_id();
+
+        if let Some(mut channel) = store.get_one(&channel_id).await {
+            channel.set_last_pin_at(self.last_pin_at);
+            store.insert_one(&channel).await;
+        }
+
+        let last_pin_at = self.last_pin_at;
+        yield Event::ChannelPinsUpdated {
+            channel_id,
+            last_pin_at,
+        };
+    }
+}
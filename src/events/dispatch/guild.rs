@@ -3,24 +3,66 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     events::{Event, StoreUpdate},
-    models::{Guild, ResourceId, UnavailableGuild},
+    models::{Channel, Guild, GuildId, Member, Message, ResourceId, UnavailableGuild},
     store::Store,
 };
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "RawGuildCreate")]
 #[non_exhaustive]
 pub struct GuildCreate {
     #[serde(flatten)]
     pub guild: Guild,
+
+    pub channels: Vec<Channel>,
+
+    /// Only includes all members of small guilds, unless the `GUILD_MEMBERS`
+    /// intent is disabled, in which case it only includes the bot's member.
+    pub members: Vec<Member>,
+}
+
+#[derive(Deserialize)]
+struct RawGuildCreate {
+    #[serde(flatten)]
+    guild: Guild,
+
+    #[serde(default)]
+    channels: Vec<Channel>,
+
+    #[serde(default)]
+    members: Vec<Member>,
+}
+
+// Channels and members in GUILD_CREATE are sent without the ID of their guild.
+impl From<RawGuildCreate> for GuildCreate {
+    fn from(mut raw: RawGuildCreate) -> Self {
+        let guild_id = raw.guild.id().id;
+
+        for channel in &mut raw.channels {
+            channel.set_guild_id(guild_id);
+        }
+
+        for member in &mut raw.members {
+            member.set_guild_id(guild_id);
+        }
+
+        Self {
+            guild: raw.guild,
+            channels: raw.channels,
+            members: raw.members,
+        }
+    }
 }
 
 impl<S> StoreUpdate<S> for GuildCreate
 where
-    S: Store<UnavailableGuild> + Store<Guild>,
+    S: Store<UnavailableGuild> + Store<Guild> + Store<Channel> + Store<Member>,
 {
     #[try_stream(boxed, ok = Event, error = anyhow::Error)]
     async fn update<'a>(&'a mut self, store: &'a S) {
         store.insert_one(&self.guild).await;
+        store.insert(&self.channels).await;
+        store.insert(&self.members).await;
 
         let guild = self.guild.clone();
 
@@ -31,3 +73,79 @@ where
         }
     }
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildUpdate {
+    #[serde(flatten)]
+    pub guild: Guild,
+}
+
+impl<S> StoreUpdate<S> for GuildUpdate
+where
+    S: Store<Guild>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let before = store.insert_one(&self.guild).await;
+
+        let after = self.guild.clone();
+        yield Event::GuildUpdated { before, after }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildDelete {
+    #[serde(flatten)]
+    pub guild: UnavailableGuild,
+
+    /// Set if the guild became unavailable due to an outage, rather than
+    /// the bot having left it.
+    #[serde(rename = "unavailable", default)]
+    pub is_unavailable: bool,
+}
+
+impl GuildDelete {
+    pub fn guild_id(&self) -> GuildId {
+        self.guild.id
+    }
+}
+
+impl<S> StoreUpdate<S> for GuildDelete
+where
+    S: Store<UnavailableGuild> + Store<Guild> + Store<Channel> + Store<Message> + Store<Member>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let guild_id = self.guild_id();
+        let guild = Store::<Guild>::remove_one(store, &guild_id).await;
+
+        if self.is_unavailable {
+            // The guild will be sent again through GUILD_CREATE once it becomes
+            // available.
+            store.insert_one(&self.guild).await;
+
+            yield Event::GuildUnavailable { guild_id, guild }
+        } else {
+            // Nothing more will be received about the guild's channels, their messages
+            // and its members, so they would never be evicted otherwise.
+            let channels = Store::<Channel>::remove_where(store, &|channel: &Channel| {
+                channel.guild_id() == Some(guild_id)
+            })
+            .await;
+            let channel_ids: Vec<_> = channels.iter().map(|channel| channel.id().id).collect();
+
+            Store::<Message>::remove_where(store, &|message: &Message| {
+                channel_ids.contains(&message.channel_id().id)
+            })
+            .await;
+            Store::<Member>::remove_where(store, &|member: &Member| {
+                member.id().guild_id() == guild_id
+            })
+            .await;
+
+            yield Event::GuildLeft { guild_id, guild }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use futures_async_stream::try_stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, StoreUpdate},
+    models::{GuildId, Member, MemberId, ResourceId, Snowflake, User},
+    store::Store,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "RawGuildMembersChunk")]
+#[non_exhaustive]
+pub struct GuildMembersChunk {
+    guild_id: Snowflake,
+    pub members: Vec<Member>,
+    pub chunk_index: u64,
+    pub chunk_count: u64,
+
+    /// IDs of requested users which were not found.
+    pub not_found: Vec<Snowflake>,
+
+    /// The nonce with which the members were requested.
+    pub nonce: Option<String>,
+}
+
+impl GuildMembersChunk {
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+
+    /// Whether this is the last chunk of its request.
+    pub fn is_last(&self) -> bool {
+        self.chunk_index + 1 >= self.chunk_count
+    }
+}
+
+#[derive(Deserialize)]
+struct RawGuildMembersChunk {
+    guild_id: Snowflake,
+    members: Vec<Member>,
+    chunk_index: u64,
+    chunk_count: u64,
+
+    #[serde(default)]
+    not_found: Vec<Snowflake>,
+
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+// Members in chunks are sent without the ID of their guild.
+impl From<RawGuildMembersChunk> for GuildMembersChunk {
+    fn from(mut raw: RawGuildMembersChunk) -> Self {
+        for member in &mut raw.members {
+            member.set_guild_id(raw.guild_id);
+        }
+
+        Self {
+            guild_id: raw.guild_id,
+            members: raw.members,
+            chunk_index: raw.chunk_index,
+            chunk_count: raw.chunk_count,
+            not_found: raw.not_found,
+            nonce: raw.nonce,
+        }
+    }
+}
+
+impl<S> StoreUpdate<S> for GuildMembersChunk
+where
+    S: Store<Member>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        store.insert(&self.members).await;
+
+        let guild_id = self.guild_id();
+        let members = self.members.clone();
+        yield Event::MembersChunk { guild_id, members };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildMemberAdd {
+    #[serde(flatten)]
+    pub member: Member,
+}
+
+impl<S> StoreUpdate<S> for GuildMemberAdd
+where
+    S: Store<Member>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        store.insert_one(&self.member).await;
+
+        let member = self.member.clone();
+        yield Event::MemberJoined { member };
+    }
+}
+
+/// The updated member, which is sent without its voice state.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildMemberUpdate {
+    #[serde(flatten)]
+    pub member: Member,
+}
+
+impl<S> StoreUpdate<S> for GuildMemberUpdate
+where
+    S: Store<Member>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let before = store.get_one(self.member.id()).await;
+
+        let mut after = self.member.clone();
+
+        if let Some(before) = &before {
+            after.is_deafened = before.is_deafened;
+            after.is_muted = before.is_muted;
+        }
+
+        store.insert_one(&after).await;
+
+        yield Event::MemberUpdated { before, after };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildMemberRemove {
+    guild_id: Snowflake,
+    pub user: User,
+}
+
+impl GuildMemberRemove {
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+
+    pub fn member_id(&self) -> MemberId {
+        MemberId::new(self.user.id().id, self.guild_id)
+    }
+}
+
+impl<S> StoreUpdate<S> for GuildMemberRemove
+where
+    S: Store<Member>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let member_id = self.member_id();
+        let member = store.remove_one(&member_id).await;
+
+        let user = self.user.clone();
+        yield Event::MemberLeft {
+            member_id,
+            user,
+            member,
+        };
+    }
+}
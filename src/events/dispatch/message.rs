@@ -1,9 +1,12 @@
+use anyhow::Result;
 use futures_async_stream::try_stream;
+use log::debug;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{
     events::{Event, StoreUpdate},
-    models::message::Message,
+    models::{message::Message, GuildId, MessageId, Snowflake, TextChannelId},
     store::Store,
 };
 
@@ -26,3 +29,146 @@ where
         yield Event::MessageSent { message };
     }
 }
+
+/// A partial message, of which only the ID is always sent.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct MessageUpdate {
+    #[serde(flatten)]
+    id: MessageId,
+
+    #[serde(flatten)]
+    fields: Map<String, Value>,
+}
+
+impl MessageUpdate {
+    pub fn message_id(&self) -> MessageId {
+        self.id
+    }
+
+    /// Applies the sent fields onto `message`, or creates a new message from
+    /// them if the message wasn't cached.
+    fn apply(&self, message: Option<&Message>) -> Result<Message> {
+        let mut value = match message {
+            Some(message) => serde_json::to_value(message)?,
+            None => serde_json::to_value(&self.id)?,
+        };
+
+        if let Value::Object(object) = &mut value {
+            object.extend(self.fields.clone());
+
+            // Marks the message as having been received now.
+            object.remove("_received_at");
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl<S> StoreUpdate<S> for MessageUpdate
+where
+    S: Store<Message>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let before = store.get_one(&self.id).await;
+
+        // Updates that don't include all fields (e.g. those which only add embeds)
+        // can't be applied to messages that weren't cached.
+        let after = match self.apply(before.as_ref()) {
+            Ok(after) => after,
+            Err(err) if before.is_none() => {
+                debug!("[MessageUpdate] Skipping uncached message {:?}", err);
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+
+        store.insert_one(&after).await;
+
+        yield Event::MessageEdited { before, after };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct MessageDelete {
+    #[serde(flatten)]
+    id: MessageId,
+
+    #[serde(default)]
+    guild_id: Option<Snowflake>,
+}
+
+impl MessageDelete {
+    pub fn message_id(&self) -> MessageId {
+        self.id
+    }
+
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId { id })
+    }
+}
+
+impl<S> StoreUpdate<S> for MessageDelete
+where
+    S: Store<Message>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let message = store.remove_one(&self.id).await;
+
+        let message_id = self.id;
+        yield Event::MessageDeleted {
+            message_id,
+            message,
+        };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct MessageDeleteBulk {
+    ids: Vec<Snowflake>,
+    channel_id: Snowflake,
+
+    #[serde(default)]
+    guild_id: Option<Snowflake>,
+}
+
+impl MessageDeleteBulk {
+    pub fn message_ids(&self) -> Vec<MessageId> {
+        self.ids
+            .iter()
+            .map(|&id| MessageId::new(id, self.channel_id))
+            .collect()
+    }
+
+    pub fn channel_id(&self) -> TextChannelId {
+        TextChannelId {
+            id: self.channel_id,
+        }
+    }
+
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId { id })
+    }
+}
+
+impl<S> StoreUpdate<S> for MessageDeleteBulk
+where
+    S: Store<Message>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let message_ids = self.message_ids();
+        let messages = store.remove(&message_ids).await;
+
+        let channel_id = self.channel_id();
+        yield Event::MessagesDeleted {
+            channel_id,
+            message_ids,
+            messages,
+        };
+    }
+}
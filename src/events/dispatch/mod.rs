@@ -1,44 +1,153 @@
 pub mod channel;
 pub mod guild;
+pub mod member;
 pub mod message;
+pub mod role;
 
 use futures_async_stream::try_stream;
-use message::MessageCreate;
-use serde::{Deserialize, Serialize};
+use message::{MessageCreate, MessageDelete, MessageDeleteBulk, MessageUpdate};
+use serde::{
+    de::{Deserializer, IgnoredAny},
+    ser::{self, Serializer},
+    Deserialize, Serialize,
+};
 
 use crate::{
     events::{Event, StoreUpdate},
+    gateway::Intents,
     models::UnavailableGuild,
     store::Store,
 };
 
-use self::guild::GuildCreate;
+use self::{
+    channel::{ChannelCreate, ChannelDelete, ChannelPinsUpdate, ChannelUpdate},
+    guild::{GuildCreate, GuildDelete, GuildUpdate},
+    member::{GuildMemberAdd, GuildMemberRemove, GuildMemberUpdate, GuildMembersChunk},
+    role::{GuildRoleCreate, GuildRoleDelete, GuildRoleUpdate},
+};
 
-// A temporary workaround for https://github.com/serde-rs/serde/issues/1714
-mod workaround {
-    use serde::{de::IgnoredAny, Deserializer};
+/// Defines `DispatchEvent` and `DispatchEventKind`, mapping each event to the
+/// name with which it is dispatched and the intents required to receive it.
+macro_rules! dispatch_events {
+    ($($name:tt => $variant:ident($data:ty): $intents:expr,)*) => {
+        #[derive(Clone, Debug)]
+        #[non_exhaustive]
+        pub enum DispatchEvent {
+            $($variant($data),)*
 
-    pub fn deserialize_unknown_event<'de, D>(deserializer: D) -> Result<(), D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_any(IgnoredAny)?;
-        Ok(())
-    }
-}
+            Unknown,
+        }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(tag = "t", content = "d", rename_all = "SCREAMING_SNAKE_CASE")]
-#[non_exhaustive]
-pub enum DispatchEvent {
-    Ready(Ready),
+        impl DispatchEvent {
+            pub fn kind(&self) -> DispatchEventKind {
+                match self {
+                    $(DispatchEvent::$variant(_) => DispatchEventKind::$variant,)*
+                    DispatchEvent::Unknown => DispatchEventKind::Unknown,
+                }
+            }
 
-    GuildCreate(GuildCreate),
+            /// The intents of which at least one must be specified to receive
+            /// this event.
+            pub fn intents(&self) -> Intents {
+                self.kind().intents()
+            }
 
-    MessageCreate(MessageCreate),
+            /// Deserializes the data (`d`) of a dispatched event of the given
+            /// kind.
+            pub(crate) fn deserialize_data<'de, D>(
+                kind: DispatchEventKind,
+                deserializer: D,
+            ) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                match kind {
+                    $(DispatchEventKind::$variant => {
+                        <$data as Deserialize>::deserialize(deserializer).map(DispatchEvent::$variant)
+                    })*
+                    DispatchEventKind::Unknown => {
+                        IgnoredAny::deserialize(deserializer)?;
+                        Ok(DispatchEvent::Unknown)
+                    }
+                }
+            }
+        }
+
+        // Only the data of the event is serialized, since its name is sent separately (as
+        // `t`).
+        impl Serialize for DispatchEvent {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                match self {
+                    $(DispatchEvent::$variant(data) => data.serialize(serializer),)*
+                    DispatchEvent::Unknown => {
+                        Err(ser::Error::custom("cannot serialize an unknown event"))
+                    }
+                }
+            }
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+        #[non_exhaustive]
+        pub enum DispatchEventKind {
+            $(
+                #[serde(rename = $name)]
+                $variant,
+            )*
+
+            #[serde(other)]
+            Unknown,
+        }
+
+        impl DispatchEventKind {
+            /// The name with which events of this kind are dispatched.
+            pub fn name(&self) -> Option<&'static str> {
+                match self {
+                    $(DispatchEventKind::$variant => Some($name),)*
+                    DispatchEventKind::Unknown => None,
+                }
+            }
+
+            /// The intents of which at least one must be specified to receive
+            /// events of this kind. Events that are always received require
+            /// no intents.
+            pub fn intents(&self) -> Intents {
+                match self {
+                    $(DispatchEventKind::$variant => $intents,)*
+                    DispatchEventKind::Unknown => Intents::empty(),
+                }
+            }
+        }
+    };
+}
 
-    #[serde(other, deserialize_with = "workaround::deserialize_unknown_event")]
-    Unknown,
+dispatch_events! {
+    "READY" => Ready(Ready): Intents::empty(),
+    "RESUMED" => Resumed(Resumed): Intents::empty(),
+    "GUILD_CREATE" => GuildCreate(GuildCreate): Intents::GUILDS,
+    "GUILD_UPDATE" => GuildUpdate(GuildUpdate): Intents::GUILDS,
+    "GUILD_DELETE" => GuildDelete(GuildDelete): Intents::GUILDS,
+    "GUILD_ROLE_CREATE" => GuildRoleCreate(GuildRoleCreate): Intents::GUILDS,
+    "GUILD_ROLE_UPDATE" => GuildRoleUpdate(GuildRoleUpdate): Intents::GUILDS,
+    "GUILD_ROLE_DELETE" => GuildRoleDelete(GuildRoleDelete): Intents::GUILDS,
+    "GUILD_MEMBERS_CHUNK" => GuildMembersChunk(GuildMembersChunk): Intents::empty(),
+    "GUILD_MEMBER_ADD" => GuildMemberAdd(GuildMemberAdd): Intents::GUILD_MEMBERS,
+    "GUILD_MEMBER_UPDATE" => GuildMemberUpdate(GuildMemberUpdate): Intents::GUILD_MEMBERS,
+    "GUILD_MEMBER_REMOVE" => GuildMemberRemove(GuildMemberRemove): Intents::GUILD_MEMBERS,
+    "CHANNEL_CREATE" => ChannelCreate(ChannelCreate): Intents::GUILDS,
+    "CHANNEL_UPDATE" => ChannelUpdate(ChannelUpdate): Intents::GUILDS,
+    "CHANNEL_DELETE" => ChannelDelete(ChannelDelete): Intents::GUILDS,
+    "CHANNEL_PINS_UPDATE" => ChannelPinsUpdate(ChannelPinsUpdate):
+        Intents::GUILDS | Intents::DIRECT_MESSAGES,
+    "MESSAGE_CREATE" => MessageCreate(MessageCreate):
+        Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES,
+    "MESSAGE_UPDATE" => MessageUpdate(MessageUpdate):
+        Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES,
+    "MESSAGE_DELETE" => MessageDelete(MessageDelete):
+        Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES,
+    "MESSAGE_DELETE_BULK" => MessageDeleteBulk(MessageDeleteBulk): Intents::GUILD_MESSAGES,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -49,6 +158,27 @@ pub struct Ready {
 
     session_id: String,
     guilds: Vec<UnavailableGuild>,
+
+    /// Not sent by older gateway versions.
+    #[serde(default)]
+    resume_gateway_url: Option<String>,
+}
+
+impl Ready {
+    /// The ID of the session, which can be used for resuming.
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// The version of the gateway API used by the session.
+    pub fn gateway_version(&self) -> u8 {
+        self.gateway_version
+    }
+
+    /// The URL of the gateway to connect to when resuming the session.
+    pub fn resume_gateway_url(&self) -> Option<&str> {
+        self.resume_gateway_url.as_deref()
+    }
 }
 
 impl<S> StoreUpdate<S> for Ready
@@ -60,3 +190,7 @@ where
         store.insert(&self.guilds).await;
     }
 }
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Resumed {}
@@ -0,0 +1,122 @@
+use futures_async_stream::try_stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Event, StoreUpdate},
+    models::{Guild, GuildId, GuildRoleId, PartialGuildRole, Snowflake},
+    store::Store,
+};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildRoleCreate {
+    guild_id: Snowflake,
+    pub role: PartialGuildRole,
+}
+
+impl GuildRoleCreate {
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+}
+
+impl<S> StoreUpdate<S> for GuildRoleCreate
+where
+    S: Store<Guild>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let guild_id = self.guild_id();
+
+        if let Some(mut guild) = store.get_one(&guild_id).await {
+            guild.roles.retain(|role| role.id != self.role.id);
+            guild.roles.push(self.role.clone());
+            store.insert_one(&guild).await;
+        }
+
+        let role = self.role.clone();
+        yield Event::RoleCreated { guild_id, role };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildRoleUpdate {
+    guild_id: Snowflake,
+    pub role: PartialGuildRole,
+}
+
+impl GuildRoleUpdate {
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+}
+
+impl<S> StoreUpdate<S> for GuildRoleUpdate
+where
+    S: Store<Guild>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let guild_id = self.guild_id();
+        let mut before = None;
+
+        if let Some(mut guild) = store.get_one(&guild_id).await {
+            match guild.roles.iter_mut().find(|role| role.id == self.role.id) {
+                Some(role) => before = Some(std::mem::replace(role, self.role.clone())),
+                None => guild.roles.push(self.role.clone()),
+            }
+
+            store.insert_one(&guild).await;
+        }
+
+        let after = self.role.clone();
+        yield Event::RoleUpdated {
+            guild_id,
+            before,
+            after,
+        };
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct GuildRoleDelete {
+    guild_id: Snowflake,
+    role_id: Snowflake,
+}
+
+impl GuildRoleDelete {
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+
+    pub fn role_id(&self) -> GuildRoleId {
+        GuildRoleId { id: self.role_id }
+    }
+}
+
+impl<S> StoreUpdate<S> for GuildRoleDelete
+where
+    S: Store<Guild>,
+{
+    #[try_stream(boxed, ok = Event, error = anyhow::Error)]
+    async fn update<'a>(&'a mut self, store: &'a S) {
+        let guild_id = self.guild_id();
+        let role_id = self.role_id();
+        let mut role = None;
+
+        if let Some(mut guild) = store.get_one(&guild_id).await {
+            if let Some(index) = guild.roles.iter().position(|role| role.id == role_id) {
+                role = Some(guild.roles.remove(index));
+                store.insert_one(&guild).await;
+            }
+        }
+
+        yield Event::RoleDeleted {
+            guild_id,
+            role_id,
+            role,
+        };
+    }
+}
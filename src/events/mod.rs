@@ -7,10 +7,18 @@ pub use self::payload::Payload;
 
 use std::pin::Pin;
 
+use chrono::{DateTime, Utc};
 use futures::{stream, Stream};
 use futures_async_stream::try_stream;
 
-use crate::{models::{Guild, UnavailableGuild, message::Message}, store::Store, util::{AsyncSink, AsyncStream}};
+use crate::{
+    models::{
+        message::Message, Channel, ChannelId, Guild, GuildId, GuildRoleId, Member, MemberId,
+        MessageId, PartialGuildRole, TextChannelId, UnavailableGuild, User,
+    },
+    store::Store,
+    util::{AsyncSink, AsyncStream},
+};
 
 use self::dispatch::DispatchEvent;
 
@@ -35,7 +43,43 @@ impl<T> PayloadDuplex for T where
 pub enum Event {
     GuildAvailable { guild: Guild },
     GuildJoined { guild: Guild },
-    MessageSent { message: Message }
+    /// `before` is the cached version of the guild, if it was cached.
+    GuildUpdated { before: Option<Guild>, after: Guild },
+    /// `guild` is the cached version of the guild, if it was cached.
+    GuildUnavailable { guild_id: GuildId, guild: Option<Guild> },
+    /// `guild` is the cached version of the guild, if it was cached.
+    GuildLeft { guild_id: GuildId, guild: Option<Guild> },
+    RoleCreated { guild_id: GuildId, role: PartialGuildRole },
+    /// `before` is the cached version of the role, if its guild was cached.
+    RoleUpdated {
+        guild_id: GuildId,
+        before: Option<PartialGuildRole>,
+        after: PartialGuildRole,
+    },
+    /// `role` is the cached version of the role, if its guild was cached.
+    RoleDeleted { guild_id: GuildId, role_id: GuildRoleId, role: Option<PartialGuildRole> },
+    MessageSent { message: Message },
+    /// `before` is the cached version of the message, if it was cached.
+    MessageEdited { before: Option<Message>, after: Message },
+    /// `message` is the cached version of the message, if it was cached.
+    MessageDeleted { message_id: MessageId, message: Option<Message> },
+    /// `messages` are the cached versions of the messages which were cached.
+    MessagesDeleted {
+        channel_id: TextChannelId,
+        message_ids: Vec<MessageId>,
+        messages: Vec<Message>,
+    },
+    MembersChunk { guild_id: GuildId, members: Vec<Member> },
+    MemberJoined { member: Member },
+    /// `before` is the cached version of the member, if it was cached.
+    MemberUpdated { before: Option<Member>, after: Member },
+    /// `member` is the cached version of the member, if it was cached.
+    MemberLeft { member_id: MemberId, user: User, member: Option<Member> },
+    ChannelCreated { channel: Channel },
+    /// `before` is the cached version of the channel, if it was cached.
+    ChannelUpdated { before: Option<Channel>, after: Channel },
+    ChannelDeleted { channel: Channel },
+    ChannelPinsUpdated { channel_id: ChannelId, last_pin_at: Option<DateTime<Utc>> },
 }
 
 pub(crate) trait StoreUpdate<S> {
@@ -45,7 +89,7 @@ pub(crate) trait StoreUpdate<S> {
 
 impl<S> StoreUpdate<S> for Payload
 where
-    S: Store<UnavailableGuild> + Store<Guild> + Store<Message>,
+    S: Store<UnavailableGuild> + Store<Guild> + Store<Channel> + Store<Message> + Store<Member>,
 {
     fn update<'a>(
         &'a mut self,
@@ -55,7 +99,23 @@ where
             Payload::Dispatch(dispatch) => match &mut dispatch.event {
                 DispatchEvent::Ready(event) => event.update(store),
                 DispatchEvent::GuildCreate(event) => event.update(store),
+                DispatchEvent::GuildUpdate(event) => event.update(store),
+                DispatchEvent::GuildDelete(event) => event.update(store),
+                DispatchEvent::GuildRoleCreate(event) => event.update(store),
+                DispatchEvent::GuildRoleUpdate(event) => event.update(store),
+                DispatchEvent::GuildRoleDelete(event) => event.update(store),
                 DispatchEvent::MessageCreate(event) => event.update(store),
+                DispatchEvent::MessageUpdate(event) => event.update(store),
+                DispatchEvent::MessageDelete(event) => event.update(store),
+                DispatchEvent::MessageDeleteBulk(event) => event.update(store),
+                DispatchEvent::GuildMembersChunk(event) => event.update(store),
+                DispatchEvent::GuildMemberAdd(event) => event.update(store),
+                DispatchEvent::GuildMemberUpdate(event) => event.update(store),
+                DispatchEvent::GuildMemberRemove(event) => event.update(store),
+                DispatchEvent::ChannelCreate(event) => event.update(store),
+                DispatchEvent::ChannelUpdate(event) => event.update(store),
+                DispatchEvent::ChannelDelete(event) => event.update(store),
+                DispatchEvent::ChannelPinsUpdate(event) => event.update(store),
 
                 _ => Box::pin(stream::empty()),
             },
@@ -1,47 +1,236 @@
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, DeserializeSeed, Deserializer, IgnoredAny, IntoDeserializer, MapAccess, Visitor},
+    ser::{self, SerializeMap},
+    Deserialize, Serialize, Serializer,
+};
+use serde_json::Value;
 
-use super::dispatch::DispatchEvent;
+use super::dispatch::{DispatchEvent, DispatchEventKind};
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(tag = "op")]
+use crate::{
+    gateway::{GatewayVersion, Intents},
+    models::{Activity, Snowflake, Status},
+};
+
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 pub enum Payload {
-    #[serde(rename = "0")]
     Dispatch(Dispatch),
-
-    #[serde(rename = "1")]
-    Heartbeat {
-        #[serde(rename = "d")]
-        data: Heartbeat,
-    },
-
-    #[serde(rename = "2")]
-    Identify {
-        #[serde(rename = "d")]
-        data: Identify,
-    },
-
-    #[serde(rename = "10")]
-    Hello {
-        #[serde(rename = "d")]
-        data: Hello,
-    },
-
-    #[serde(rename = "11")]
+    Heartbeat { data: Heartbeat },
+    Identify { data: Identify },
+    UpdatePresence { data: UpdatePresence },
+    Resume { data: Resume },
+    Reconnect,
+    RequestGuildMembers { data: RequestGuildMembers },
+    InvalidSession { data: InvalidSession },
+    Hello { data: Hello },
     HeartbeatAck,
+    Unknown,
+}
+
+impl Serialize for Payload {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct OpPayload<'a, T> {
+            op: u8,
+
+            #[serde(rename = "d")]
+            data: &'a T,
+        }
+
+        #[derive(Serialize)]
+        struct DispatchPayload<'a> {
+            op: u8,
+
+            #[serde(rename = "s")]
+            seqnum: u64,
+
+            #[serde(rename = "t")]
+            name: Option<&'static str>,
+
+            #[serde(rename = "d")]
+            data: &'a DispatchEvent,
+        }
+
+        match self {
+            Payload::Dispatch(dispatch) => DispatchPayload {
+                op: 0,
+                seqnum: dispatch.seqnum,
+                name: dispatch.event.kind().name(),
+                data: &dispatch.event,
+            }
+            .serialize(serializer),
+            Payload::Heartbeat { data } => OpPayload { op: 1, data }.serialize(serializer),
+            Payload::Identify { data } => OpPayload { op: 2, data }.serialize(serializer),
+            Payload::UpdatePresence { data } => OpPayload { op: 3, data }.serialize(serializer),
+            Payload::Resume { data } => OpPayload { op: 6, data }.serialize(serializer),
+            Payload::Reconnect => OpPayload { op: 7, data: &() }.serialize(serializer),
+            Payload::RequestGuildMembers { data } => {
+                OpPayload { op: 8, data }.serialize(serializer)
+            }
+            Payload::InvalidSession { data } => OpPayload { op: 9, data }.serialize(serializer),
+            Payload::Hello { data } => OpPayload { op: 10, data }.serialize(serializer),
+            Payload::HeartbeatAck => OpPayload { op: 11, data: &() }.serialize(serializer),
+            Payload::Unknown => Err(ser::Error::custom("cannot serialize an unknown payload")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(PayloadVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier)]
+enum PayloadField {
+    #[serde(rename = "op")]
+    Opcode,
+
+    #[serde(rename = "d")]
+    Data,
+
+    #[serde(rename = "s")]
+    Seqnum,
+
+    #[serde(rename = "t")]
+    Kind,
 
     #[serde(other)]
-    Unknown,
+    Other,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+struct PayloadVisitor;
+
+impl<'de> Visitor<'de> for PayloadVisitor {
+    type Value = Payload;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a gateway payload")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut opcode = None;
+        let mut seqnum = None;
+        let mut kind = None;
+        let mut payload = None;
+        let mut buffered_data = None;
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                PayloadField::Opcode => opcode = Some(map.next_value()?),
+                PayloadField::Seqnum => seqnum = map.next_value()?,
+                PayloadField::Kind => kind = map.next_value()?,
+                PayloadField::Data => match opcode {
+                    // The data can only be deserialized directly if its type is already known,
+                    // which is the case if it is sent after the opcode (and event name). This
+                    // is the order in which the gateway sends JSON payloads.
+                    Some(opcode) if opcode != 0 || kind.is_some() => {
+                        payload = Some(map.next_value_seed(PayloadSeed { opcode, kind })?)
+                    }
+                    _ => buffered_data = Some(map.next_value::<Value>()?),
+                },
+                PayloadField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let opcode = opcode.ok_or_else(|| de::Error::missing_field("op"))?;
+
+        let mut payload = match (payload, buffered_data) {
+            (Some(payload), _) => payload,
+            (None, Some(data)) => PayloadSeed { opcode, kind }
+                .deserialize(data)
+                .map_err(de::Error::custom)?,
+            (None, None) => PayloadSeed { opcode, kind }.deserialize(().into_deserializer())?,
+        };
+
+        if let Payload::Dispatch(dispatch) = &mut payload {
+            dispatch.seqnum = seqnum.ok_or_else(|| de::Error::missing_field("s"))?;
+        }
+
+        Ok(payload)
+    }
+}
+
+/// Deserializes the data (`d`) of a payload with a known opcode.
+///
+/// The sequence number of dispatches isn't necessarily known yet at this
+/// point, and must be filled in afterwards.
+struct PayloadSeed {
+    opcode: u8,
+    kind: Option<DispatchEventKind>,
+}
+
+impl<'de> DeserializeSeed<'de> for PayloadSeed {
+    type Value = Payload;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let payload = match self.opcode {
+            0 => {
+                let kind = self.kind.ok_or_else(|| de::Error::missing_field("t"))?;
+                let event = DispatchEvent::deserialize_data(kind, deserializer)?;
+
+                Payload::Dispatch(Dispatch { seqnum: 0, event })
+            }
+            1 => Payload::Heartbeat {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            2 => Payload::Identify {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            3 => Payload::UpdatePresence {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            6 => Payload::Resume {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            7 => {
+                IgnoredAny::deserialize(deserializer)?;
+                Payload::Reconnect
+            }
+            8 => Payload::RequestGuildMembers {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            9 => Payload::InvalidSession {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            10 => Payload::Hello {
+                data: Deserialize::deserialize(deserializer)?,
+            },
+            11 => {
+                IgnoredAny::deserialize(deserializer)?;
+                Payload::HeartbeatAck
+            }
+            _ => {
+                IgnoredAny::deserialize(deserializer)?;
+                Payload::Unknown
+            }
+        };
+
+        Ok(payload)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Dispatch {
-    #[serde(rename = "s")]
     pub seqnum: u64,
-
-    #[serde(flatten)]
     pub event: DispatchEvent,
 }
 
@@ -57,21 +246,148 @@ pub struct Identify {
     #[serde(rename = "compress")]
     #[serde(default)]
     pub use_payload_compression: Option<bool>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<ShardInfo>,
+
+    pub intents: Intents,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence: Option<UpdatePresence>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Identifies a shard as one of a number of shards over which guilds are
+/// distributed. Sent as `[id, count]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "(u64, u64)", into = "(u64, u64)")]
+pub struct ShardInfo {
+    pub id: u64,
+    pub count: u64,
+}
+
+impl From<(u64, u64)> for ShardInfo {
+    fn from((id, count): (u64, u64)) -> Self {
+        Self { id, count }
+    }
+}
+
+impl From<ShardInfo> for (u64, u64) {
+    fn from(info: ShardInfo) -> Self {
+        (info.id, info.count)
+    }
+}
+
+/// The presence of the bot, either sent on its own or as part of Identify.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct UpdatePresence {
+    /// The time (in milliseconds since the Unix epoch) at which the bot went
+    /// idle.
+    pub since: Option<u64>,
+
+    pub activities: Vec<Activity>,
+    pub status: Status,
+
+    #[serde(rename = "afk")]
+    pub is_afk: bool,
+}
+
+impl UpdatePresence {
+    pub fn new(status: Status, activities: impl IntoIterator<Item = Activity>) -> Self {
+        Self {
+            since: None,
+            activities: activities.into_iter().collect(),
+            status,
+            is_afk: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
 #[non_exhaustive]
 pub struct IdentifyProperties {
-    #[serde(rename = "$os")]
+    #[serde(alias = "$os")]
     pub os: String,
 
-    #[serde(rename = "$browser")]
+    #[serde(alias = "$browser")]
     pub browser: String,
 
-    #[serde(rename = "$device")]
+    #[serde(alias = "$device")]
     pub device: String,
+
+    /// The version of the gateway to which the properties are sent, which
+    /// determines their keys.
+    #[serde(skip)]
+    pub version: GatewayVersion,
+}
+
+impl Serialize for IdentifyProperties {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Keys are prefixed with `$` before v10, which only accepts them without it.
+        let keys = if self.version >= GatewayVersion::V10 {
+            ["os", "browser", "device"]
+        } else {
+            ["$os", "$browser", "$device"]
+        };
+
+        let mut map = serializer.serialize_map(Some(keys.len()))?;
+        map.serialize_entry(keys[0], &self.os)?;
+        map.serialize_entry(keys[1], &self.browser)?;
+        map.serialize_entry(keys[2], &self.device)?;
+        map.end()
+    }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Resume {
+    pub token: String,
+    pub session_id: String,
+
+    #[serde(rename = "seq")]
+    pub seqnum: u64,
+}
+
+/// Requests members of a guild, which are sent in `GUILD_MEMBERS_CHUNK`
+/// dispatches.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct RequestGuildMembers {
+    pub guild_id: Snowflake,
+
+    /// Requests members whose username starts with this string, or all
+    /// members if it is empty.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+
+    /// The maximum number of members to send, or 0 for no limit.
+    #[serde(default)]
+    pub limit: u64,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presences: Option<bool>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_ids: Option<Vec<Snowflake>>,
+
+    /// Identifies the chunks sent in response to this request.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// Whether or not the invalidated session can be resumed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InvalidSession(pub bool);
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[non_exhaustive]
 pub struct Hello {
@@ -83,3 +399,46 @@ impl Hello {
         Duration::from_millis(self.heartbeat_interval)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn properties(version: GatewayVersion) -> IdentifyProperties {
+        IdentifyProperties {
+            os: "linux".to_owned(),
+            browser: "crate".to_owned(),
+            device: "crate".to_owned(),
+            version,
+        }
+    }
+
+    #[test]
+    fn prefixes_identify_properties_before_v10() {
+        for &version in &[GatewayVersion::V6, GatewayVersion::V8] {
+            assert_eq!(
+                serde_json::to_value(properties(version)).unwrap(),
+                json!({ "$os": "linux", "$browser": "crate", "$device": "crate" })
+            );
+        }
+
+        assert_eq!(
+            serde_json::to_value(properties(GatewayVersion::V10)).unwrap(),
+            json!({ "os": "linux", "browser": "crate", "device": "crate" })
+        );
+    }
+
+    #[test]
+    fn decodes_identify_properties_of_every_version() {
+        for &version in &[GatewayVersion::V6, GatewayVersion::V8, GatewayVersion::V10] {
+            let value = serde_json::to_value(properties(version)).unwrap();
+            let decoded: IdentifyProperties = serde_json::from_value(value).unwrap();
+
+            assert_eq!(decoded.os, "linux");
+            assert_eq!(decoded.browser, "crate");
+            assert_eq!(decoded.device, "crate");
+        }
+    }
+}
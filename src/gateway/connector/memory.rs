@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+
+use super::{Frame, GatewayConnectionParams, GatewayConnector, GatewayTransport};
+use crate::models::Gateway;
+
+/// One end of an in-memory connection, whose frames are received by the
+/// other end.
+#[derive(Debug)]
+pub struct MemoryTransport {
+    incoming: UnboundedReceiver<Frame>,
+    outgoing: UnboundedSender<Frame>,
+}
+
+impl MemoryTransport {
+    /// Creates both ends of a connection.
+    pub fn pair() -> (Self, Self) {
+        let (client_tx, server_rx) = mpsc::unbounded();
+        let (server_tx, client_rx) = mpsc::unbounded();
+
+        let client = Self {
+            incoming: client_rx,
+            outgoing: client_tx,
+        };
+        let server = Self {
+            incoming: server_rx,
+            outgoing: server_tx,
+        };
+
+        (client, server)
+    }
+}
+
+#[async_trait]
+impl GatewayTransport for MemoryTransport {
+    async fn send(&mut self, frame: Frame) -> Result<()> {
+        self.outgoing
+            .unbounded_send(frame)
+            .map_err(|_| anyhow!("Connection closed by the other end"))
+    }
+
+    async fn receive(&mut self) -> Option<Result<Frame>> {
+        self.incoming.next().await.map(Ok)
+    }
+}
+
+/// A connector whose connections are accepted by a [`MemoryGatewayListener`]
+/// rather than by the gateway, e.g. to drive a shard from a scripted server.
+#[derive(Clone, Debug)]
+pub struct MemoryGatewayConnector {
+    connections: UnboundedSender<(MemoryTransport, GatewayConnectionParams)>,
+}
+
+impl MemoryGatewayConnector {
+    pub fn new() -> (Self, MemoryGatewayListener) {
+        let (connections_tx, connections_rx) = mpsc::unbounded();
+
+        let connector = Self {
+            connections: connections_tx,
+        };
+        let listener = MemoryGatewayListener {
+            connections: connections_rx,
+        };
+
+        (connector, listener)
+    }
+}
+
+#[async_trait]
+impl GatewayConnector for MemoryGatewayConnector {
+    type Transport = MemoryTransport;
+
+    async fn connect(
+        &self,
+        _gateway: Gateway,
+        conn_params: GatewayConnectionParams,
+    ) -> Result<Self::Transport> {
+        let (client, server) = MemoryTransport::pair();
+
+        self.connections
+            .unbounded_send((server, conn_params))
+            .map_err(|_| anyhow!("Listener was dropped"))?;
+
+        Ok(client)
+    }
+}
+
+/// Receives the server ends of the connections opened by a
+/// [`MemoryGatewayConnector`], along with the parameters with which they were
+/// opened.
+#[derive(Debug)]
+pub struct MemoryGatewayListener {
+    connections: UnboundedReceiver<(MemoryTransport, GatewayConnectionParams)>,
+}
+
+impl MemoryGatewayListener {
+    /// Waits for the next connection, or returns `None` once every connector
+    /// has been dropped.
+    pub async fn accept(&mut self) -> Option<(MemoryTransport, GatewayConnectionParams)> {
+        self.connections.next().await
+    }
+}
@@ -1,36 +1,105 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use async_tungstenite::{
-    tokio::connect_async,
-    tungstenite::{Error as WsError, Message as WsMessage},
+    tokio::{connect_async, ConnectStream},
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame as WsCloseFrame},
+        Error as WsError, Message as WsMessage,
+    },
+    WebSocketStream,
+};
+use futures::{
+    io::{AsyncRead, AsyncWrite},
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
 };
-use futures::{Sink, Stream, StreamExt};
 use serde_urlencoded;
 use url::Url;
 
-use super::{GatewayConnectionParams, GatewayConnector, GatewayResult, GatewayStream};
-use crate::models::Gateway;
+use super::{CloseFrame, Frame, GatewayConnectionParams, GatewayConnector, GatewayTransport};
+use crate::{gateway::GatewayError, models::Gateway};
 
+#[derive(Clone, Copy, Debug, Default)]
 pub struct WsGatewayConnector;
 
 #[async_trait]
 impl GatewayConnector for WsGatewayConnector {
-    type Input = impl Stream<Item = GatewayResult>;
-    type Output = impl Sink<WsMessage, Error = WsError>;
+    type Transport = WsTransport<ConnectStream>;
 
     async fn connect(
         &self,
         gateway: Gateway,
         conn_params: GatewayConnectionParams,
-    ) -> Result<GatewayStream<Self::Input, Self::Output>> {
+    ) -> Result<Self::Transport> {
+        let invalid_url = |reason: String| GatewayError::InvalidUrl {
+            url: gateway.url().to_owned(),
+            reason,
+        };
+
         let query_params = serde_urlencoded::to_string(conn_params)?;
-        let mut url = Url::parse(gateway.url())?;
+        let mut url = Url::parse(gateway.url()).map_err(|err| invalid_url(err.to_string()))?;
         url.set_query(Some(query_params.as_str()));
 
-        let (ws_stream, _) = connect_async(url.as_str()).await?;
+        let (ws_stream, _) = match connect_async(url.as_str()).await {
+            Ok(res) => res,
+            Err(WsError::Url(err)) => return Err(invalid_url(err.to_string()).into()),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(WsTransport::new(ws_stream))
+    }
+}
+
+/// A [`GatewayTransport`] over a WebSocket connection.
+pub struct WsTransport<S> {
+    stream: SplitStream<WebSocketStream<S>>,
+    sink: SplitSink<WebSocketStream<S>, WsMessage>,
+}
 
+impl<S> WsTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    pub fn new(ws_stream: WebSocketStream<S>) -> Self {
         let (sink, stream) = ws_stream.split();
 
-        Ok(GatewayStream::new(stream, sink))
+        Self { stream, sink }
+    }
+}
+
+#[async_trait]
+impl<S> GatewayTransport for WsTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    async fn send(&mut self, frame: Frame) -> Result<()> {
+        let message = match frame {
+            Frame::Text(text) => WsMessage::Text(text),
+            Frame::Binary(bytes) => WsMessage::Binary(bytes),
+            Frame::Close(frame) => WsMessage::Close(frame.map(|frame| WsCloseFrame {
+                code: CloseCode::from(frame.code),
+                reason: frame.reason.into(),
+            })),
+        };
+
+        Ok(self.sink.send(message).await?)
+    }
+
+    async fn receive(&mut self) -> Option<Result<Frame>> {
+        loop {
+            let frame = match self.stream.next().await? {
+                Ok(WsMessage::Text(text)) => Frame::Text(text),
+                Ok(WsMessage::Binary(bytes)) => Frame::Binary(bytes),
+                Ok(WsMessage::Close(frame)) => Frame::Close(frame.map(|frame| CloseFrame {
+                    code: frame.code.into(),
+                    reason: frame.reason.into_owned(),
+                })),
+                // Pings are answered by tungstenite itself.
+                Ok(WsMessage::Ping(_)) | Ok(WsMessage::Pong(_)) => continue,
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            return Some(Ok(frame));
+        }
     }
 }
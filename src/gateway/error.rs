@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum GatewayCloseCode {
+    UnknownError,
+    UnknownOpcode,
+    DecodeError,
+    NotAuthenticated,
+    AuthenticationFailed,
+    AlreadyAuthenticated,
+    InvalidSeqnum,
+    Ratelimited,
+    SessionTimeout,
+    InvalidShard,
+    ShardingRequired,
+    InvalidVersion,
+    InvalidIntents,
+    UnauthorizedIntents,
+    Unknown,
+}
+
+impl From<u16> for GatewayCloseCode {
+    fn from(code: u16) -> Self {
+        match code {
+            4000 => GatewayCloseCode::UnknownError,
+            4001 => GatewayCloseCode::UnknownOpcode,
+            4002 => GatewayCloseCode::DecodeError,
+            4003 => GatewayCloseCode::NotAuthenticated,
+            4004 => GatewayCloseCode::AuthenticationFailed,
+            4005 => GatewayCloseCode::AlreadyAuthenticated,
+            4007 => GatewayCloseCode::InvalidSeqnum,
+            4008 => GatewayCloseCode::Ratelimited,
+            4009 => GatewayCloseCode::SessionTimeout,
+            4010 => GatewayCloseCode::InvalidShard,
+            4011 => GatewayCloseCode::ShardingRequired,
+            4012 => GatewayCloseCode::InvalidVersion,
+            4013 => GatewayCloseCode::InvalidIntents,
+            4014 => GatewayCloseCode::UnauthorizedIntents,
+
+            // Codes added after this was written, or which aren't gateway close codes
+            // at all (e.g. standard WebSocket close codes)
+            _ => GatewayCloseCode::Unknown,
+        }
+    }
+}
+
+/// An error that a shard could not handle on its own.
+///
+/// Errors of this type are returned by [`Shard`](super::Shard) and
+/// [`Runner`](crate::client::Runner) as an [`anyhow::Error`], from which they
+/// can be retrieved using `downcast_ref`.
+#[derive(Clone, Debug, Error)]
+#[non_exhaustive]
+pub enum GatewayError {
+    /// The gateway closed the connection.
+    #[error("Gateway closed the connection with {code:?}: {reason:?}")]
+    Closed {
+        code: GatewayCloseCode,
+        reason: String,
+        is_recoverable: bool,
+    },
+
+    /// The URL of the gateway is invalid.
+    #[error("Invalid gateway URL {url:?}: {reason}")]
+    InvalidUrl { url: String, reason: String },
+
+    /// Sending a command would exceed the gateway's rate limit.
+    #[error("Sending a command would exceed the rate limit, retry after {retry_after:?}")]
+    Ratelimited { retry_after: Duration },
+}
+
+impl GatewayError {
+    /// Whether the shard can recover from this error by reconnecting, which it
+    /// will do the next time it is polled.
+    ///
+    /// Unrecoverable errors are caused by invalid configuration (e.g. an
+    /// invalid token, intents or gateway URL) and will reoccur until it is
+    /// fixed.
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            GatewayError::Closed { is_recoverable, .. } => *is_recoverable,
+            GatewayError::InvalidUrl { .. } => false,
+            GatewayError::Ratelimited { .. } => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_unknown_close_codes() {
+        assert_eq!(
+            GatewayCloseCode::from(4004),
+            GatewayCloseCode::AuthenticationFailed
+        );
+        assert_eq!(GatewayCloseCode::from(4006), GatewayCloseCode::Unknown);
+        assert_eq!(GatewayCloseCode::from(4999), GatewayCloseCode::Unknown);
+        assert_eq!(GatewayCloseCode::from(1000), GatewayCloseCode::Unknown);
+        assert_eq!(GatewayCloseCode::from(u16::MAX), GatewayCloseCode::Unknown);
+    }
+}
@@ -0,0 +1,453 @@
+use std::{convert::TryInto, str};
+
+use serde::{
+    de::{
+        self, value::SeqDeserializer, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+        SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize,
+};
+
+use super::{
+    error::{Error, Result},
+    tag, FORMAT_VERSION,
+};
+
+/// Deserializes an instance of `T` from a versioned ETF term.
+pub fn from_slice<'de, T>(bytes: &'de [u8]) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_slice(bytes)?;
+    let value = T::deserialize(&mut deserializer)?;
+
+    deserializer.end()?;
+
+    Ok(value)
+}
+
+pub struct Deserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Deserializer<'de> {
+    /// Creates a deserializer from a versioned ETF term.
+    pub fn from_slice(input: &'de [u8]) -> Result<Self> {
+        let mut deserializer = Self { input };
+
+        match deserializer.read_u8()? {
+            FORMAT_VERSION => Ok(deserializer),
+            version => Err(Error::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Ensures that the entire input has been consumed.
+    pub fn end(&self) -> Result<()> {
+        if self.input.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TrailingInput)
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'de [u8]> {
+        if self.input.len() < len {
+            return Err(Error::UnexpectedEof);
+        }
+
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_f64(&mut self) -> Result<f64> {
+        Ok(f64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    fn peek_tag(&self) -> Result<u8> {
+        self.input.first().copied().ok_or(Error::UnexpectedEof)
+    }
+
+    fn read_str(&mut self, len: usize) -> Result<&'de str> {
+        str::from_utf8(self.read_bytes(len)?).map_err(de::Error::custom)
+    }
+
+    /// Reads the name of an atom with the given (already consumed) tag.
+    fn read_atom(&mut self, tag: u8) -> Result<&'de str> {
+        let len = match tag {
+            tag::ATOM_EXT | tag::ATOM_UTF8_EXT => self.read_u16()? as usize,
+            tag::SMALL_ATOM_EXT | tag::SMALL_ATOM_UTF8_EXT => self.read_u8()? as usize,
+            tag => return Err(Error::UnsupportedTag(tag)),
+        };
+
+        self.read_str(len)
+    }
+
+    /// Whether the next term is the `nil` atom, which represents the absence
+    /// of a value.
+    fn is_nil(&self) -> bool {
+        let mut peek = Deserializer { input: self.input };
+
+        match peek.read_u8() {
+            Ok(tag) => matches!(peek.read_atom(tag), Ok("nil")),
+            Err(_) => false,
+        }
+    }
+
+    /// Reads a big integer with the given number of digits.
+    fn read_big<V>(&mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let sign = self.read_u8()?;
+        let digits = self.read_bytes(len)?;
+
+        if digits.len() > 8 {
+            return Err(Error::IntegerOutOfRange);
+        }
+
+        // Digits are stored in little-endian order.
+        let magnitude = digits
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &digit| (acc << 8) | digit as u64);
+
+        if sign == 0 {
+            visitor.visit_u64(magnitude)
+        } else if magnitude <= i64::MAX as u64 + 1 {
+            visitor.visit_i64((magnitude as i64).wrapping_neg())
+        } else {
+            Err(Error::IntegerOutOfRange)
+        }
+    }
+
+    /// Visits the elements of a list or tuple with the given length.
+    fn visit_list<V>(&mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut access = ListAccess::new(self, len);
+        let value = visitor.visit_seq(&mut access)?;
+
+        access.end(len).map(|_| value)
+    }
+
+    /// Visits the entries of a map with the given number of entries.
+    fn visit_map<V>(&mut self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut access = ListAccess::new(self, len);
+        let value = visitor.visit_map(&mut access)?;
+
+        access.end(len).map(|_| value)
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.read_u8()? {
+            tag::SMALL_INTEGER_EXT => visitor.visit_u8(self.read_u8()?),
+            tag::INTEGER_EXT => visitor.visit_i32(self.read_i32()?),
+            tag::NEW_FLOAT_EXT => visitor.visit_f64(self.read_f64()?),
+            tag::FLOAT_EXT => {
+                // Old-style floats are stored as a null-padded string of 31 bytes.
+                let float = self.read_str(31)?.trim_end_matches('\0');
+
+                visitor.visit_f64(float.parse().map_err(de::Error::custom)?)
+            }
+            tag @ tag::ATOM_EXT
+            | tag @ tag::SMALL_ATOM_EXT
+            | tag @ tag::ATOM_UTF8_EXT
+            | tag @ tag::SMALL_ATOM_UTF8_EXT => match self.read_atom(tag)? {
+                "nil" => visitor.visit_unit(),
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                atom => visitor.visit_borrowed_str(atom),
+            },
+            tag::SMALL_BIG_EXT => {
+                let len = self.read_u8()? as usize;
+                self.read_big(len, visitor)
+            }
+            tag::LARGE_BIG_EXT => {
+                let len = self.read_u32()? as usize;
+                self.read_big(len, visitor)
+            }
+            tag::BINARY_EXT => {
+                let len = self.read_u32()? as usize;
+                let bytes = self.read_bytes(len)?;
+
+                match str::from_utf8(bytes) {
+                    Ok(string) => visitor.visit_borrowed_str(string),
+                    Err(_) => visitor.visit_borrowed_bytes(bytes),
+                }
+            }
+            tag::STRING_EXT => {
+                // Lists of small integers are encoded as a sequence of bytes.
+                let len = self.read_u16()? as usize;
+                let bytes = self.read_bytes(len)?;
+
+                visitor.visit_seq(SeqDeserializer::new(bytes.iter().copied()))
+            }
+            tag::NIL_EXT => self.visit_list(0, visitor),
+            tag::LIST_EXT => {
+                let len = self.read_u32()? as usize;
+                let value = self.visit_list(len, visitor)?;
+
+                // Only proper lists, whose tail is an empty list, are supported.
+                match self.read_u8()? {
+                    tag::NIL_EXT => Ok(value),
+                    tag => Err(Error::UnsupportedTag(tag)),
+                }
+            }
+            tag::SMALL_TUPLE_EXT => {
+                let len = self.read_u8()? as usize;
+                self.visit_list(len, visitor)
+            }
+            tag::LARGE_TUPLE_EXT => {
+                let len = self.read_u32()? as usize;
+                self.visit_list(len, visitor)
+            }
+            tag::MAP_EXT => {
+                let len = self.read_u32()? as usize;
+                self.visit_map(len, visitor)
+            }
+            tag => Err(Error::UnsupportedTag(tag)),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.is_nil() {
+            self.deserialize_any(de::IgnoredAny)?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_tag()? {
+            tag::BINARY_EXT => {
+                self.read_u8()?;
+                let len = self.read_u32()? as usize;
+
+                visitor.visit_borrowed_bytes(self.read_bytes(len)?)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.peek_tag()? {
+            // Variants with data are encoded as a map with a single entry.
+            tag::MAP_EXT => {
+                self.read_u8()?;
+
+                match self.read_u32()? {
+                    1 => visitor.visit_enum(Enum::new(self, false)),
+                    _ => Err(de::Error::custom("expected a map with a single entry")),
+                }
+            }
+            // Unit variants are encoded as their name.
+            _ => visitor.visit_enum(Enum::new(self, true)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+/// Provides access to the elements of a list or tuple, or the entries of a
+/// map.
+struct ListAccess<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+}
+
+impl<'a, 'de> ListAccess<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, len: usize) -> Self {
+        Self { de, remaining: len }
+    }
+
+    /// Ensures that every element has been visited, since the remaining
+    /// elements would otherwise be mistaken for subsequent terms.
+    fn end(&self, len: usize) -> Result<()> {
+        match self.remaining {
+            0 => Ok(()),
+            remaining => Err(de::Error::invalid_length(
+                len,
+                &format!("{} elements", len - remaining).as_str(),
+            )),
+        }
+    }
+}
+
+impl<'a, 'de> SeqAccess<'de> for ListAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'a, 'de> MapAccess<'de> for ListAccess<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Provides access to an enum variant, which is either encoded as its name (for
+/// unit variants) or as a map from its name to its data.
+struct Enum<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    is_unit: bool,
+}
+
+impl<'a, 'de> Enum<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, is_unit: bool) -> Self {
+        Self { de, is_unit }
+    }
+}
+
+impl<'a, 'de> EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self.de)?;
+
+        Ok((variant, self))
+    }
+}
+
+impl<'a, 'de> VariantAccess<'de> for Enum<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        if self.is_unit {
+            Ok(())
+        } else {
+            de::Deserialize::deserialize(self.de)
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.is_unit {
+            seed.deserialize(().into_deserializer())
+        } else {
+            seed.deserialize(self.de)
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
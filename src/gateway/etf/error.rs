@@ -0,0 +1,43 @@
+use std::fmt::Display;
+
+use serde::{de, ser};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("{0}")]
+    Message(String),
+
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+
+    #[error("Unexpected trailing input")]
+    TrailingInput,
+
+    #[error("Unsupported format version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unsupported term tag {0}")]
+    UnsupportedTag(u8),
+
+    #[error("Integer out of range")]
+    IntegerOutOfRange,
+
+    #[error("Term is too large to be encoded")]
+    TermTooLarge,
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
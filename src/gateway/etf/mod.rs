@@ -0,0 +1,316 @@
+//! Serialization and deserialization of the Erlang External Term Format
+//! (ETF).
+//!
+//! Only the subset of the format which is used by the gateway is supported.
+//! Strings are encoded as binaries, `None` and `()` as the `nil` atom and
+//! integers which don't fit into 32 bits (such as snowflakes) as big integers.
+
+mod de;
+mod error;
+mod ser;
+
+pub use self::de::{from_slice, Deserializer};
+pub use self::error::{Error, Result};
+pub use self::ser::{to_vec, Serializer};
+
+/// The version byte which precedes every encoded term.
+const FORMAT_VERSION: u8 = 131;
+
+mod tag {
+    pub const NEW_FLOAT_EXT: u8 = 70;
+    pub const SMALL_INTEGER_EXT: u8 = 97;
+    pub const INTEGER_EXT: u8 = 98;
+    pub const FLOAT_EXT: u8 = 99;
+    pub const ATOM_EXT: u8 = 100;
+    pub const SMALL_TUPLE_EXT: u8 = 104;
+    pub const LARGE_TUPLE_EXT: u8 = 105;
+    pub const NIL_EXT: u8 = 106;
+    pub const STRING_EXT: u8 = 107;
+    pub const LIST_EXT: u8 = 108;
+    pub const BINARY_EXT: u8 = 109;
+    pub const SMALL_BIG_EXT: u8 = 110;
+    pub const LARGE_BIG_EXT: u8 = 111;
+    pub const SMALL_ATOM_EXT: u8 = 115;
+    pub const MAP_EXT: u8 = 116;
+    pub const ATOM_UTF8_EXT: u8 = 118;
+    pub const SMALL_ATOM_UTF8_EXT: u8 = 119;
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{events::Payload, gateway::test_support, models::Snowflake};
+
+    const GUILD_ID: u64 = 81384788765712384;
+    const CHANNEL_ID: u64 = 381870553235193857;
+    const USER_ID: u64 = 80351110224678912;
+    const MESSAGE_ID: u64 = 334385199974967042;
+    const ROLE_ID: u64 = 41771983423143936;
+
+    fn user() -> Value {
+        json!({
+            "id": USER_ID.to_string(),
+            "username": "Nelly",
+            "discriminator": "1337",
+            "avatar": null,
+        })
+    }
+
+    fn member() -> Value {
+        json!({
+            "guild_id": GUILD_ID.to_string(),
+            "user": user(),
+            "nick": "Nel",
+            "roles": [ROLE_ID.to_string()],
+            "joined_at": "2021-01-01T00:00:00Z",
+            "deaf": false,
+            "mute": true,
+        })
+    }
+
+    fn role() -> Value {
+        json!({
+            "id": ROLE_ID.to_string(),
+            "name": "Moderator",
+            "color": 3447003,
+            "position": 1,
+            "permissions": "66321471",
+            "hoist": true,
+            "managed": false,
+            "mentionable": false,
+        })
+    }
+
+    fn channel() -> Value {
+        json!({
+            "id": CHANNEL_ID.to_string(),
+            "type": 0,
+            "guild_id": GUILD_ID.to_string(),
+            "position": 6,
+            "name": "general",
+            "nsfw": false,
+            "last_message_id": MESSAGE_ID.to_string(),
+        })
+    }
+
+    /// The name and data of every event which can be dispatched.
+    fn dispatches() -> Vec<(&'static str, Value)> {
+        vec![
+            (
+                "READY",
+                json!({
+                    "v": 8,
+                    "session_id": "session",
+                    "guilds": [{ "id": GUILD_ID.to_string(), "unavailable": true }],
+                    "resume_gateway_url": "wss://gateway.discord.gg",
+                }),
+            ),
+            ("RESUMED", json!({})),
+            ("GUILD_CREATE", {
+                let mut guild = test_support::guild_create(GUILD_ID, "Guild", vec![channel()]);
+                guild["members"] = json!([member()]);
+                guild
+            }),
+            (
+                "GUILD_UPDATE",
+                test_support::guild_create(GUILD_ID, "Guild", Vec::new()),
+            ),
+            (
+                "GUILD_DELETE",
+                json!({ "id": GUILD_ID.to_string(), "unavailable": true }),
+            ),
+            (
+                "GUILD_ROLE_CREATE",
+                json!({ "guild_id": GUILD_ID.to_string(), "role": role() }),
+            ),
+            (
+                "GUILD_ROLE_UPDATE",
+                json!({ "guild_id": GUILD_ID.to_string(), "role": role() }),
+            ),
+            (
+                "GUILD_ROLE_DELETE",
+                json!({ "guild_id": GUILD_ID.to_string(), "role_id": ROLE_ID.to_string() }),
+            ),
+            (
+                "GUILD_MEMBERS_CHUNK",
+                json!({
+                    "guild_id": GUILD_ID.to_string(),
+                    "members": [member()],
+                    "chunk_index": 0,
+                    "chunk_count": 1,
+                    "not_found": [],
+                    "nonce": "nonce",
+                }),
+            ),
+            ("GUILD_MEMBER_ADD", member()),
+            ("GUILD_MEMBER_UPDATE", member()),
+            (
+                "GUILD_MEMBER_REMOVE",
+                json!({ "guild_id": GUILD_ID.to_string(), "user": user() }),
+            ),
+            ("CHANNEL_CREATE", channel()),
+            ("CHANNEL_UPDATE", channel()),
+            ("CHANNEL_DELETE", channel()),
+            (
+                "CHANNEL_PINS_UPDATE",
+                json!({
+                    "guild_id": GUILD_ID.to_string(),
+                    "channel_id": CHANNEL_ID.to_string(),
+                    "last_pin_timestamp": "2021-01-01T00:00:00Z",
+                }),
+            ),
+            (
+                "MESSAGE_CREATE",
+                json!({
+                    "id": MESSAGE_ID.to_string(),
+                    "channel_id": CHANNEL_ID.to_string(),
+                    "type": 0,
+                    "content": "Hello",
+                    "timestamp": "2021-01-01T00:00:00Z",
+                    "edited_timestamp": null,
+                }),
+            ),
+            (
+                "MESSAGE_UPDATE",
+                json!({
+                    "id": MESSAGE_ID.to_string(),
+                    "channel_id": CHANNEL_ID.to_string(),
+                    "content": "Hello again",
+                    "edited_timestamp": "2021-01-01T00:01:00Z",
+                }),
+            ),
+            (
+                "MESSAGE_DELETE",
+                json!({
+                    "id": MESSAGE_ID.to_string(),
+                    "channel_id": CHANNEL_ID.to_string(),
+                    "guild_id": GUILD_ID.to_string(),
+                }),
+            ),
+            (
+                "MESSAGE_DELETE_BULK",
+                json!({
+                    "ids": [MESSAGE_ID.to_string()],
+                    "channel_id": CHANNEL_ID.to_string(),
+                    "guild_id": GUILD_ID.to_string(),
+                }),
+            ),
+        ]
+    }
+
+    #[test]
+    fn round_trips_dispatches() {
+        for (name, data) in dispatches() {
+            let payload: Payload =
+                serde_json::from_value(json!({ "op": 0, "s": 42, "t": name, "d": data })).unwrap();
+
+            let bytes = to_vec(&payload).unwrap();
+            let decoded: Payload = from_slice(&bytes)
+                .unwrap_or_else(|err| panic!("Failed to decode {}: {}", name, err));
+
+            match &decoded {
+                Payload::Dispatch(dispatch) => {
+                    assert_eq!(dispatch.event.kind().name(), Some(name));
+                    assert_eq!(dispatch.seqnum, 42);
+                }
+                other => panic!("Expected {}, decoded {:?}", name, other),
+            }
+
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&payload).unwrap(),
+                "{} changed after a round trip",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_small_big_snowflakes() {
+        let mut bytes = vec![FORMAT_VERSION, tag::SMALL_BIG_EXT, 8, 0];
+        bytes.extend_from_slice(&GUILD_ID.to_le_bytes());
+
+        let snowflake: Snowflake = from_slice(&bytes).unwrap();
+        let expected: Snowflake = serde_json::from_value(json!(GUILD_ID.to_string())).unwrap();
+
+        assert_eq!(snowflake, expected);
+        assert_eq!(to_vec(&snowflake).unwrap(), bytes);
+    }
+
+    #[test]
+    fn decodes_atoms() {
+        let atom = |tag: u8, name: &str| {
+            let mut bytes = vec![FORMAT_VERSION, tag];
+
+            match tag {
+                tag::ATOM_EXT | tag::ATOM_UTF8_EXT => {
+                    bytes.extend_from_slice(&(name.len() as u16).to_be_bytes())
+                }
+                _ => bytes.push(name.len() as u8),
+            }
+
+            bytes.extend_from_slice(name.as_bytes());
+            bytes
+        };
+
+        for &tag in &[
+            tag::ATOM_EXT,
+            tag::SMALL_ATOM_EXT,
+            tag::ATOM_UTF8_EXT,
+            tag::SMALL_ATOM_UTF8_EXT,
+        ] {
+            assert_eq!(from_slice::<Option<u64>>(&atom(tag, "nil")).unwrap(), None);
+            assert!(from_slice::<()>(&atom(tag, "nil")).is_ok());
+            assert!(from_slice::<bool>(&atom(tag, "true")).unwrap());
+            assert!(!from_slice::<bool>(&atom(tag, "false")).unwrap());
+            assert_eq!(
+                from_slice::<String>(&atom(tag, "online")).unwrap(),
+                "online"
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_data_before_opcode() {
+        // Maps are decoded in the order in which their entries are encoded.
+        #[derive(Serialize)]
+        struct Reordered {
+            d: Value,
+            t: Option<&'static str>,
+            s: Option<u64>,
+            op: u8,
+        }
+
+        let ready = to_vec(&Reordered {
+            d: dispatches().remove(0).1,
+            t: Some("READY"),
+            s: Some(1),
+            op: 0,
+        })
+        .unwrap();
+
+        match from_slice(&ready).unwrap() {
+            Payload::Dispatch(dispatch) => {
+                assert_eq!(dispatch.event.kind().name(), Some("READY"));
+                assert_eq!(dispatch.seqnum, 1);
+            }
+            other => panic!("Expected READY, decoded {:?}", other),
+        }
+
+        let hello = to_vec(&Reordered {
+            d: json!({ "heartbeat_interval": 41250 }),
+            t: None,
+            s: None,
+            op: 10,
+        })
+        .unwrap();
+
+        match from_slice(&hello).unwrap() {
+            Payload::Hello { .. } => {}
+            other => panic!("Expected Hello, decoded {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,469 @@
+use std::convert::TryFrom;
+
+use serde::{ser, Serialize};
+
+use super::{
+    error::{Error, Result},
+    tag, FORMAT_VERSION,
+};
+
+/// Serializes `value` as a versioned ETF term.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    let mut serializer = Serializer::new();
+    value.serialize(&mut serializer)?;
+
+    Ok(serializer.into_inner())
+}
+
+pub struct Serializer {
+    output: Vec<u8>,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        Self {
+            output: vec![FORMAT_VERSION],
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.output
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.output.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_len(&mut self, len: usize) -> Result<()> {
+        let len = u32::try_from(len).map_err(|_| Error::TermTooLarge)?;
+        self.write_u32(len);
+
+        Ok(())
+    }
+
+    fn write_atom(&mut self, name: &str) {
+        // Atoms are only used for a handful of short, fixed names.
+        self.output.push(tag::SMALL_ATOM_UTF8_EXT);
+        self.output.push(name.len() as u8);
+        self.output.extend_from_slice(name.as_bytes());
+    }
+
+    fn write_binary(&mut self, bytes: &[u8]) -> Result<()> {
+        self.output.push(tag::BINARY_EXT);
+        self.write_len(bytes.len())?;
+        self.output.extend_from_slice(bytes);
+
+        Ok(())
+    }
+
+    fn write_signed(&mut self, value: i64) {
+        match value {
+            0..=255 => {
+                self.output.push(tag::SMALL_INTEGER_EXT);
+                self.output.push(value as u8);
+            }
+            _ if i32::try_from(value).is_ok() => {
+                self.output.push(tag::INTEGER_EXT);
+                self.output.extend_from_slice(&(value as i32).to_be_bytes());
+            }
+            _ => self.write_big(value < 0, value.wrapping_abs() as u64),
+        }
+    }
+
+    fn write_unsigned(&mut self, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.write_signed(value),
+            Err(_) => self.write_big(false, value),
+        }
+    }
+
+    fn write_big(&mut self, is_negative: bool, magnitude: u64) {
+        // Digits are stored in little-endian order, without trailing zeroes.
+        let digits = magnitude.to_le_bytes();
+        let len = digits
+            .iter()
+            .rposition(|&digit| digit != 0)
+            .map_or(0, |i| i + 1);
+
+        self.output.push(tag::SMALL_BIG_EXT);
+        self.output.push(len as u8);
+        self.output.push(is_negative as u8);
+        self.output.extend_from_slice(&digits[..len]);
+    }
+
+    /// Writes the header of a single-entry map, which is used to encode enum
+    /// variants with data.
+    fn write_variant(&mut self, variant: &'static str) -> Result<()> {
+        self.output.push(tag::MAP_EXT);
+        self.write_u32(1);
+        self.write_binary(variant.as_bytes())
+    }
+
+    /// Starts a list or map whose length is written once it is known.
+    fn start_compound(&mut self, kind: CompoundKind) -> Compound<'_> {
+        let start = self.output.len();
+
+        self.output.push(match kind {
+            CompoundKind::List => tag::LIST_EXT,
+            CompoundKind::Map => tag::MAP_EXT,
+        });
+        self.write_u32(0);
+
+        Compound {
+            ser: self,
+            kind,
+            start,
+            len: 0,
+        }
+    }
+}
+
+impl Default for Serializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.write_atom(if v { "true" } else { "false" });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write_signed(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.write_unsigned(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.output.push(tag::NEW_FLOAT_EXT);
+        self.output.extend_from_slice(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_binary(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_binary(v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.write_atom("nil");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.write_variant(variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(self.start_compound(CompoundKind::List))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        // Tuples are encoded as lists, since the gateway expects lists for
+        // fixed-length arrays.
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.write_variant(variant)?;
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(self.start_compound(CompoundKind::Map))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.write_variant(variant)?;
+        self.serialize_map(Some(len))
+    }
+}
+
+enum CompoundKind {
+    List,
+    Map,
+}
+
+/// Serializes the elements of a list or the entries of a map.
+///
+/// Since the number of elements isn't always known in advance (e.g. when
+/// fields are flattened or skipped), the length is written once the compound
+/// has ended.
+pub struct Compound<'a> {
+    ser: &'a mut Serializer,
+    kind: CompoundKind,
+    start: usize,
+    len: usize,
+}
+
+impl<'a> Compound<'a> {
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<()> {
+        let len = u32::try_from(self.len).map_err(|_| Error::TermTooLarge)?;
+
+        match self.kind {
+            // Empty lists are encoded as a standalone nil term.
+            CompoundKind::List if len == 0 => {
+                self.ser.output.truncate(self.start);
+                self.ser.output.push(tag::NIL_EXT);
+            }
+            CompoundKind::List => {
+                self.ser.output[self.start + 1..self.start + 5].copy_from_slice(&len.to_be_bytes());
+                // Proper lists end with an empty list as their tail.
+                self.ser.output.push(tag::NIL_EXT);
+            }
+            CompoundKind::Map => {
+                self.ser.output[self.start + 1..self.start + 5].copy_from_slice(&len.to_be_bytes());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.len += 1;
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.len += 1;
+        self.element(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use futures::{
+    channel::{
+        mpsc::{self, UnboundedSender},
+        oneshot,
+    },
+    StreamExt,
+};
+use tokio::time::timeout;
+
+use super::{ratelimit::CommandRatelimiter, GatewayError, SessionInfo, ShardLatency};
+
+use crate::{
+    events::{
+        dispatch::member::GuildMembersChunk,
+        payload::{Payload, RequestGuildMembers, ShardInfo, UpdatePresence},
+    },
+    models::{GuildId, Member},
+};
+
+/// The maximum time to wait for the next chunk of a member request.
+const MEMBER_CHUNK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A command sent to a shard through its [`ShardHandle`].
+#[derive(Debug)]
+pub(crate) enum ShardCommand {
+    /// Sends a payload. If `is_reserved` is set, the payload already took
+    /// capacity from the rate limiter.
+    Send { payload: Payload, is_reserved: bool },
+}
+
+/// A request to shut down a shard, which replies with its session if it was
+/// kept resumable.
+#[derive(Debug)]
+pub(crate) struct CloseRequest {
+    pub(crate) is_resumable: bool,
+    pub(crate) reply: oneshot::Sender<Option<SessionInfo>>,
+}
+
+/// State shared between a shard and its handles.
+#[derive(Debug, Default)]
+pub(crate) struct ShardShared {
+    ratelimiter: Mutex<CommandRatelimiter>,
+    latency: Mutex<ShardLatency>,
+    pub(crate) is_closed: AtomicBool,
+    next_nonce: AtomicU64,
+
+    /// Member requests awaiting chunks, keyed by nonce.
+    member_requests: Mutex<HashMap<String, UnboundedSender<GuildMembersChunk>>>,
+}
+
+impl ShardShared {
+    pub(crate) fn ratelimiter(&self) -> MutexGuard<'_, CommandRatelimiter> {
+        self.ratelimiter.lock().unwrap()
+    }
+
+    pub(crate) fn latency(&self) -> MutexGuard<'_, ShardLatency> {
+        self.latency.lock().unwrap()
+    }
+
+    /// Forwards a member chunk to the request it belongs to, if it is still
+    /// being awaited.
+    pub(crate) fn dispatch_member_chunk(&self, chunk: &GuildMembersChunk) {
+        let nonce = match &chunk.nonce {
+            Some(nonce) => nonce,
+            None => return,
+        };
+
+        let mut member_requests = self.member_requests.lock().unwrap();
+
+        if let Some(sender) = member_requests.get(nonce) {
+            if sender.unbounded_send(chunk.clone()).is_err() || chunk.is_last() {
+                member_requests.remove(nonce);
+            }
+        }
+    }
+}
+
+/// A handle to a running shard, through which payloads can be sent to the
+/// gateway while the shard is being polled elsewhere.
+///
+/// Commands are queued until the shard's session is active.
+#[derive(Clone, Debug)]
+pub struct ShardHandle {
+    shard_info: Option<ShardInfo>,
+    commands: UnboundedSender<ShardCommand>,
+    close_requests: UnboundedSender<CloseRequest>,
+    shared: Arc<ShardShared>,
+}
+
+impl ShardHandle {
+    pub(crate) fn new(
+        shard_info: Option<ShardInfo>,
+        commands: UnboundedSender<ShardCommand>,
+        close_requests: UnboundedSender<CloseRequest>,
+    ) -> Self {
+        Self {
+            shard_info,
+            commands,
+            close_requests,
+            shared: Default::default(),
+        }
+    }
+
+    pub(crate) fn with_shard_info(&mut self, shard_info: Option<ShardInfo>) -> &mut Self {
+        self.shard_info = shard_info;
+        self
+    }
+
+    pub(crate) fn shared(&self) -> &ShardShared {
+        &self.shared
+    }
+
+    /// The ID of the shard and the total number of shards, if the bot is
+    /// sharded.
+    pub fn shard_info(&self) -> Option<ShardInfo> {
+        self.shard_info
+    }
+
+    /// Statistics on the round-trip times of the shard's heartbeats.
+    pub fn latency(&self) -> ShardLatency {
+        self.shared.latency().clone()
+    }
+
+    /// Queues a payload to be sent by the shard, which delays it as long as
+    /// necessary to stay within the gateway's rate limit.
+    pub fn send(&self, payload: Payload) -> Result<()> {
+        self.send_command(ShardCommand::Send {
+            payload,
+            is_reserved: false,
+        })
+    }
+
+    /// Queues a payload to be sent by the shard if this can be done without
+    /// exceeding the gateway's rate limit, and otherwise returns
+    /// [`GatewayError::Ratelimited`].
+    pub fn try_send(&self, payload: Payload) -> Result<()> {
+        self.shared
+            .ratelimiter()
+            .try_acquire()
+            .map_err(|retry_after| GatewayError::Ratelimited { retry_after })?;
+
+        self.send_command(ShardCommand::Send {
+            payload,
+            is_reserved: true,
+        })
+    }
+
+    fn send_command(&self, command: ShardCommand) -> Result<()> {
+        self.commands
+            .unbounded_send(command)
+            .map_err(|_| anyhow!("Shard has been dropped"))
+    }
+
+    /// Shuts down the shard once it is next polled. See [`Shard::shutdown`].
+    ///
+    /// [`Shard::shutdown`]: super::Shard::shutdown
+    pub async fn shutdown(&self, is_resumable: bool) -> Result<Option<SessionInfo>> {
+        // A closed shard no longer handles requests.
+        if self.shared.is_closed.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+
+        let (reply, session) = oneshot::channel();
+
+        let request = CloseRequest {
+            is_resumable,
+            reply,
+        };
+
+        if self.close_requests.unbounded_send(request).is_err() {
+            // The shard may have been closed since it was checked above.
+            if self.shared.is_closed.load(Ordering::Acquire) {
+                return Ok(None);
+            }
+
+            return Err(anyhow!("Shard has been dropped"));
+        }
+
+        session.await.map_err(|_| anyhow!("Shard has been dropped"))
+    }
+
+    /// Updates the bot's presence on this shard.
+    pub fn update_presence(&self, presence: UpdatePresence) -> Result<()> {
+        self.send(Payload::UpdatePresence { data: presence })
+    }
+
+    /// Requests the members of a guild whose username starts with `query` (or
+    /// all members if it is empty), up to `limit` members (or all if it is 0).
+    ///
+    /// The guild must belong to this shard. Requesting all members requires
+    /// the `GUILD_MEMBERS` intent. The members are also inserted into the
+    /// member store as their chunks are received by the runner.
+    pub async fn request_members(
+        &self,
+        guild_id: GuildId,
+        query: impl Into<String>,
+        limit: u64,
+    ) -> Result<Vec<Member>> {
+        let nonce = format!(
+            "{}-{}",
+            self.shard_info.map_or(0, |info| info.id),
+            self.shared.next_nonce.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let (chunk_tx, mut chunks) = mpsc::unbounded();
+
+        self.shared
+            .member_requests
+            .lock()
+            .unwrap()
+            .insert(nonce.clone(), chunk_tx);
+
+        let result = self
+            .collect_members(guild_id, query.into(), limit, &nonce, &mut chunks)
+            .await;
+
+        self.shared.member_requests.lock().unwrap().remove(&nonce);
+
+        result
+    }
+
+    async fn collect_members(
+        &self,
+        guild_id: GuildId,
+        query: String,
+        limit: u64,
+        nonce: &str,
+        chunks: &mut mpsc::UnboundedReceiver<GuildMembersChunk>,
+    ) -> Result<Vec<Member>> {
+        self.send(Payload::RequestGuildMembers {
+            data: RequestGuildMembers {
+                guild_id: guild_id.id,
+                query: Some(query),
+                limit,
+                presences: None,
+                user_ids: None,
+                nonce: Some(nonce.to_string()),
+            },
+        })?;
+
+        let mut members = Vec::new();
+
+        loop {
+            let chunk = timeout(MEMBER_CHUNK_TIMEOUT, chunks.next())
+                .await
+                .map_err(|_| anyhow!("Timed out waiting for member chunks"))?
+                .ok_or_else(|| anyhow!("Shard has been dropped"))?;
+
+            members.extend(chunk.members.iter().cloned());
+
+            if chunk.is_last() {
+                return Ok(members);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        gateway::{
+            test_support::{FakeGateway, ScriptedConnection, Step},
+            GatewayCompression, Shard,
+        },
+        util::AsyncStream,
+    };
+
+    fn member(user_id: u64, username: &str) -> Value {
+        json!({
+            "user": {
+                "id": user_id.to_string(),
+                "username": username,
+                "discriminator": "0001",
+            },
+            "roles": [],
+            "joined_at": "2021-01-01T00:00:00+00:00",
+        })
+    }
+
+    fn chunk(nonce: &str, chunk_index: u64, chunk_count: u64, members: Vec<Value>) -> Value {
+        json!({
+            "guild_id": "81384788765712384",
+            "members": members,
+            "chunk_index": chunk_index,
+            "chunk_count": chunk_count,
+            "nonce": nonce,
+        })
+    }
+
+    #[tokio::test]
+    async fn collects_member_chunks_of_request() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway.script(
+            ScriptedConnection::new()
+                .hello(Duration::from_secs(40))
+                .expect_identify()
+                .ready("session", &gateway.url())
+                .step(Step::Expect(8))
+                .dispatch(
+                    "GUILD_MEMBERS_CHUNK",
+                    chunk("0-0", 0, 2, vec![member(1, "first")]),
+                )
+                // A chunk of a request made by another process.
+                .dispatch(
+                    "GUILD_MEMBERS_CHUNK",
+                    chunk("other", 0, 1, vec![member(3, "foreign")]),
+                )
+                .dispatch(
+                    "GUILD_MEMBERS_CHUNK",
+                    chunk("0-0", 1, 2, vec![member(2, "second")]),
+                ),
+        );
+
+        let mut shard = Shard::new(
+            gateway.gateway(),
+            "token".to_owned(),
+            Default::default(),
+            GatewayCompression::None,
+            connector,
+        );
+        let handle = shard.handle();
+
+        let guild_id: GuildId =
+            serde_json::from_value(json!({ "id": "81384788765712384" })).unwrap();
+
+        let members = tokio::select! {
+            members = handle.request_members(guild_id, "", 0) => members.unwrap(),
+            _ = async { while shard.next().await.is_some() {} } => panic!("The shard was closed"),
+        };
+
+        let usernames: Vec<_> = members
+            .iter()
+            .map(|member| member.user.username.as_str())
+            .collect();
+
+        assert_eq!(usernames, ["first", "second"]);
+        assert!(handle.shared.member_requests.lock().unwrap().is_empty());
+    }
+}
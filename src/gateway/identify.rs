@@ -0,0 +1,143 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use log::debug;
+use tokio::{
+    sync::Mutex,
+    time::{sleep_until, Instant},
+};
+
+/// Coordinates when shards may identify, so that the gateway's session start
+/// rate limit isn't exceeded.
+///
+/// Shards are grouped into `max_concurrency` buckets by
+/// `shard_id % max_concurrency`, and only one shard per bucket may identify
+/// every [`IDENTIFY_INTERVAL`]. Deployments running shards across multiple
+/// processes can implement this trait on top of an external coordinator.
+#[async_trait]
+pub trait IdentifyQueue: Send + Sync {
+    /// Waits until the shard with the given ID is allowed to identify.
+    ///
+    /// The shard completes the returned permit once it has sent its identify,
+    /// which may be well after the wait if connecting takes a while.
+    async fn wait(&self, shard_id: u64) -> IdentifyPermit;
+}
+
+/// Permission for a shard to identify, returned by [`IdentifyQueue::wait`].
+///
+/// Dropping the permit without completing it gives up the slot without the
+/// shard having identified.
+pub struct IdentifyPermit {
+    on_identify: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl IdentifyPermit {
+    /// Creates a permit which calls `on_identify` when the shard identifies.
+    pub fn new(on_identify: impl FnOnce() + Send + Sync + 'static) -> Self {
+        Self {
+            on_identify: Some(Box::new(on_identify)),
+        }
+    }
+
+    /// Marks that the shard has sent its identify.
+    pub fn complete(mut self) {
+        if let Some(on_identify) = self.on_identify.take() {
+            on_identify();
+        }
+    }
+}
+
+impl fmt::Debug for IdentifyPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentifyPermit").finish()
+    }
+}
+
+/// The interval between identifies of shards in the same bucket.
+pub const IDENTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An [`IdentifyQueue`] for shards running in the same process.
+pub struct LocalIdentifyQueue {
+    buckets: Vec<Arc<Mutex<Option<Instant>>>>,
+}
+
+impl LocalIdentifyQueue {
+    pub fn new(max_concurrency: u64) -> Self {
+        Self {
+            buckets: (0..max_concurrency.max(1))
+                .map(|_| Default::default())
+                .collect(),
+        }
+    }
+}
+
+impl Default for LocalIdentifyQueue {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[async_trait]
+impl IdentifyQueue for LocalIdentifyQueue {
+    async fn wait(&self, shard_id: u64) -> IdentifyPermit {
+        let bucket = (shard_id % self.buckets.len() as u64) as usize;
+
+        // The lock is held until the permit is completed or dropped, so shards in the
+        // same bucket are let through one at a time in the order in which they
+        // started waiting.
+        let mut last_identify = self.buckets[bucket].clone().lock_owned().await;
+
+        if let Some(last_identify) = *last_identify {
+            debug!(
+                "[IdentifyQueue] Shard {} waiting for bucket {}",
+                shard_id, bucket
+            );
+
+            sleep_until(last_identify + IDENTIFY_INTERVAL).await;
+        }
+
+        IdentifyPermit::new(move || *last_identify = Some(Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spaces_identifies_from_when_they_are_sent() {
+        tokio::time::pause();
+
+        let queue = LocalIdentifyQueue::default();
+        let start = Instant::now();
+
+        // The first shard takes a while to connect before identifying.
+        let permit = queue.wait(0).await;
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        permit.complete();
+
+        queue.wait(1).await.complete();
+        assert_eq!(start.elapsed(), Duration::from_secs(3) + IDENTIFY_INTERVAL);
+
+        // A permit which is dropped without identifying doesn't delay the next one.
+        let identified_at = Instant::now();
+        drop(queue.wait(2).await);
+        queue.wait(3).await.complete();
+        assert_eq!(identified_at.elapsed(), IDENTIFY_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn buckets_identify_concurrently() {
+        tokio::time::pause();
+
+        let queue = LocalIdentifyQueue::new(2);
+        let start = Instant::now();
+
+        queue.wait(0).await.complete();
+        queue.wait(1).await.complete();
+        assert_eq!(start.elapsed(), Duration::from_secs(0));
+
+        queue.wait(2).await.complete();
+        assert_eq!(start.elapsed(), IDENTIFY_INTERVAL);
+    }
+}
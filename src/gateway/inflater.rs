@@ -0,0 +1,132 @@
+use anyhow::Result;
+use flate2::{Decompress, FlushDecompress};
+
+/// Inflates messages received over a zlib-stream compressed connection.
+///
+/// The gateway compresses every message sent over a connection using the same
+/// zlib context, so a new `Inflater` must be used for each connection.
+pub(crate) struct Inflater {
+    decompress: Decompress,
+    buffer: Vec<u8>,
+}
+
+impl Inflater {
+    /// The suffix with which the gateway terminates each compressed message.
+    const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+    /// The number of bytes by which the output buffer is grown while inflating.
+    const CHUNK_SIZE: usize = 32 * 1024;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            decompress: Decompress::new(true),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Buffers a received frame, returning the inflated message once all of its
+    /// frames have been received.
+    pub(crate) fn push(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.buffer.extend_from_slice(frame);
+
+        if !self.buffer.ends_with(&Self::ZLIB_SUFFIX) {
+            return Ok(None);
+        }
+
+        // The buffer is cleared even if the message can't be inflated, so that it isn't
+        // prepended to the next one.
+        let output = self.inflate();
+        self.buffer.clear();
+
+        output.map(Some)
+    }
+
+    fn inflate(&mut self) -> Result<Vec<u8>> {
+        let mut input = self.buffer.as_slice();
+        let mut output = Vec::with_capacity(Self::CHUNK_SIZE);
+
+        loop {
+            if output.len() == output.capacity() {
+                output.reserve(Self::CHUNK_SIZE);
+            }
+
+            let total_in = self.decompress.total_in();
+            let total_out = self.decompress.total_out();
+
+            self.decompress
+                .decompress_vec(input, &mut output, FlushDecompress::Sync)?;
+
+            let consumed = (self.decompress.total_in() - total_in) as usize;
+            let produced = (self.decompress.total_out() - total_out) as usize;
+
+            input = &input[consumed..];
+
+            // The message has been fully inflated once all of the input has been consumed
+            // without filling the output buffer.
+            if (input.is_empty() && output.len() < output.capacity())
+                || (consumed == 0 && produced == 0)
+            {
+                break;
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compress, Compression, FlushCompress};
+
+    use super::*;
+
+    /// Compresses messages the way the gateway does, with a single zlib context
+    /// flushed after each message.
+    fn compress(messages: &[&[u8]]) -> Vec<Vec<u8>> {
+        let mut compress = Compress::new(Compression::default(), true);
+
+        messages
+            .iter()
+            .map(|message| {
+                let mut output = Vec::with_capacity(message.len() + 64);
+                compress
+                    .compress_vec(message, &mut output, FlushCompress::Sync)
+                    .unwrap();
+                output
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inflates_messages_sharing_a_context() {
+        let messages: [&[u8]; 2] = [b"{\"op\":10}", b"{\"op\":11}"];
+        let mut inflater = Inflater::new();
+
+        for (frame, message) in compress(&messages).iter().zip(messages.iter()) {
+            assert!(frame.ends_with(&Inflater::ZLIB_SUFFIX));
+            assert_eq!(inflater.push(frame).unwrap().as_deref(), Some(*message));
+        }
+    }
+
+    #[test]
+    fn waits_for_suffix_of_split_message() {
+        let message = vec![b'a'; 100_000];
+        let frame = compress(&[&message]).remove(0);
+        let (first, second) = frame.split_at(frame.len() - 2);
+
+        let mut inflater = Inflater::new();
+
+        assert_eq!(inflater.push(first).unwrap(), None);
+        assert_eq!(inflater.push(second).unwrap(), Some(message));
+    }
+
+    #[test]
+    fn clears_buffer_on_error() {
+        let mut inflater = Inflater::new();
+        let mut corrupt = vec![0xff; 16];
+        corrupt.extend_from_slice(&Inflater::ZLIB_SUFFIX);
+
+        assert!(inflater.push(&corrupt).is_err());
+        assert!(inflater.buffer.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+use bitflags::bitflags;
+use serde::{
+    de::{self, Deserialize, Deserializer, Visitor},
+    Serialize, Serializer,
+};
+
+bitflags! {
+    /// The groups of events that a shard subscribes to.
+    ///
+    /// Events belonging to intents that aren't specified in Identify are not
+    /// sent by the gateway. Privileged intents must additionally be enabled for
+    /// the bot in the developer portal.
+    #[derive(Default)]
+    pub struct Intents: u64 {
+        const GUILDS = 1 << 0;
+        /// Privileged.
+        const GUILD_MEMBERS = 1 << 1;
+        const GUILD_BANS = 1 << 2;
+        const GUILD_EMOJIS = 1 << 3;
+        const GUILD_INTEGRATIONS = 1 << 4;
+        const GUILD_WEBHOOKS = 1 << 5;
+        const GUILD_INVITES = 1 << 6;
+        const GUILD_VOICE_STATES = 1 << 7;
+        /// Privileged.
+        const GUILD_PRESENCES = 1 << 8;
+        const GUILD_MESSAGES = 1 << 9;
+        const GUILD_MESSAGE_REACTIONS = 1 << 10;
+        const GUILD_MESSAGE_TYPING = 1 << 11;
+        const DIRECT_MESSAGES = 1 << 12;
+        const DIRECT_MESSAGE_REACTIONS = 1 << 13;
+        const DIRECT_MESSAGE_TYPING = 1 << 14;
+        /// Privileged. Required to receive message content since gateway v10.
+        const MESSAGE_CONTENT = 1 << 15;
+    }
+}
+
+impl Intents {
+    /// The intents which must be enabled in the developer portal before they
+    /// can be used.
+    pub fn privileged() -> Self {
+        Self::GUILD_MEMBERS | Self::GUILD_PRESENCES | Self::MESSAGE_CONTENT
+    }
+
+    /// All intents which can be used without being enabled in the developer
+    /// portal.
+    pub fn non_privileged() -> Self {
+        Self::all() - Self::privileged()
+    }
+}
+
+impl Serialize for Intents {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for Intents {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct IntentsVisitor;
+
+        impl<'de> Visitor<'de> for IntentsVisitor {
+            type Value = Intents;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("intents as an integer")
+            }
+
+            fn visit_u64<E>(self, bits: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                // Unknown intents are ignored, since they may be added at any time.
+                Ok(Intents::from_bits_truncate(bits))
+            }
+        }
+
+        deserializer.deserialize_u64(IntentsVisitor)
+    }
+}
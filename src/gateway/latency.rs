@@ -0,0 +1,56 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Statistics on the round-trip times of a shard's heartbeats, over a rolling
+/// window of the most recent heartbeats.
+#[derive(Clone, Debug, Default)]
+pub struct ShardLatency {
+    samples: VecDeque<Duration>,
+}
+
+impl ShardLatency {
+    /// The number of heartbeats over which statistics are computed.
+    pub const WINDOW_SIZE: usize = 64;
+
+    pub(crate) fn record(&mut self, latency: Duration) {
+        if self.samples.len() == Self::WINDOW_SIZE {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(latency);
+    }
+
+    /// The round-trip time of the most recently acknowledged heartbeat.
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    /// The average round-trip time.
+    pub fn average(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let total: Duration = self.samples.iter().sum();
+
+        Some(total / self.samples.len() as u32)
+    }
+
+    /// The 99th percentile of the round-trip times.
+    pub fn p99(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut samples: Vec<_> = self.samples.iter().copied().collect();
+        samples.sort();
+
+        let index = (samples.len() * 99 + 99) / 100 - 1;
+
+        Some(samples[index])
+    }
+
+    /// The round-trip times in the window, from oldest to most recent.
+    pub fn samples(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.samples.iter().copied()
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use tokio::time::sleep;
+
+use super::{
+    GatewayCompression, GatewayConnector, GatewayVersion, IdentifyQueue, Intents,
+    LocalIdentifyQueue, PayloadEncoding, SessionInfo, Shard, WsGatewayConnector,
+};
+
+use crate::{
+    events::payload::{ShardInfo, UpdatePresence},
+    models::BotGateway,
+};
+
+/// Creates the shards necessary for connecting a bot to the gateway, based on
+/// the information returned by [`BotGateway`].
+pub struct ShardManager<C: GatewayConnector + Clone + Send = WsGatewayConnector> {
+    gateway: BotGateway,
+    token: String,
+    shard_count: u64,
+    intents: Intents,
+    version: GatewayVersion,
+    encoding: PayloadEncoding,
+    compression: GatewayCompression,
+    connector: C,
+    identify_queue: Arc<dyn IdentifyQueue>,
+    presence: Option<UpdatePresence>,
+    sessions: Vec<SessionInfo>,
+}
+
+impl ShardManager<WsGatewayConnector> {
+    pub fn default_with(gateway: BotGateway, token: String) -> Self {
+        Self::new(
+            gateway,
+            token,
+            Intents::non_privileged(),
+            Default::default(),
+            Default::default(),
+            WsGatewayConnector,
+        )
+    }
+}
+
+impl<C: GatewayConnector + Clone + Send + Sync> ShardManager<C> {
+    pub fn new(
+        gateway: BotGateway,
+        token: String,
+        intents: Intents,
+        encoding: PayloadEncoding,
+        compression: GatewayCompression,
+        connector: C,
+    ) -> Self {
+        let shard_count = gateway.recommended_shard_count().max(1);
+        let identify_queue = Arc::new(LocalIdentifyQueue::new(
+            *gateway.session_start_limit().max_concurrency(),
+        ));
+
+        Self {
+            gateway,
+            token,
+            shard_count,
+            intents,
+            version: Default::default(),
+            encoding,
+            compression,
+            connector,
+            identify_queue,
+            presence: None,
+            sessions: Default::default(),
+        }
+    }
+
+    /// Overrides the number of shards recommended by the gateway.
+    pub fn with_shard_count(&mut self, shard_count: u64) -> &mut Self {
+        self.shard_count = shard_count.max(1);
+        self
+    }
+
+    /// Replaces the in-process identify queue, e.g. with one that is shared
+    /// with other processes.
+    pub fn with_identify_queue(&mut self, identify_queue: Arc<dyn IdentifyQueue>) -> &mut Self {
+        self.identify_queue = identify_queue;
+        self
+    }
+
+    /// Sets the version of the gateway API with which all shards connect.
+    pub fn with_version(&mut self, version: GatewayVersion) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the presence with which all shards identify.
+    pub fn with_presence(&mut self, presence: Option<UpdatePresence>) -> &mut Self {
+        self.presence = presence;
+        self
+    }
+
+    /// Sets sessions to resume, e.g. those returned by
+    /// [`RunnerHandle::shutdown`](crate::client::RunnerHandle::shutdown)
+    /// before a restart. Shards with a session don't count against the
+    /// session start limit.
+    ///
+    /// Sessions are matched to shards by their shard ID when spawning, and
+    /// ignored if they were started with a different shard count.
+    pub fn with_sessions(&mut self, sessions: impl IntoIterator<Item = SessionInfo>) -> &mut Self {
+        self.sessions = sessions.into_iter().collect();
+        self
+    }
+
+    /// The session to resume for the shard with the given ID, if any.
+    fn session(&self, shard_id: u64) -> Option<&SessionInfo> {
+        self.sessions.iter().find(|session| {
+            session.shard_info
+                == Some(ShardInfo {
+                    id: shard_id,
+                    count: self.shard_count,
+                })
+        })
+    }
+
+    /// The number of shards that will be spawned.
+    pub fn shard_count(&self) -> u64 {
+        self.shard_count
+    }
+
+    /// Creates all shards.
+    ///
+    /// Every shard without a session to resume starts a new session, so if the
+    /// remaining number of session starts is insufficient, this waits until
+    /// the limit resets.
+    pub async fn spawn(&self) -> Result<Vec<Shard<C>>> {
+        let limit = self.gateway.session_start_limit();
+        let session_starts = (0..self.shard_count)
+            .filter(|&id| self.session(id).is_none())
+            .count() as u64;
+
+        if *limit.total() < session_starts {
+            return Err(anyhow!(
+                "Cannot start {} sessions with a session start limit of {}",
+                session_starts,
+                limit.total()
+            ));
+        }
+
+        if *limit.remaining() < session_starts {
+            warn!(
+                "[ShardManager] Only {} of {} session starts remaining, waiting {:?} for the limit to reset",
+                limit.remaining(),
+                session_starts,
+                limit.resets_after()
+            );
+
+            sleep(limit.resets_after()).await;
+        }
+
+        debug!("[ShardManager] Spawning {} shards", self.shard_count);
+
+        let shards = (0..self.shard_count)
+            .map(|id| {
+                let mut shard = Shard::new(
+                    self.gateway.gateway(),
+                    self.token.clone(),
+                    self.encoding.clone(),
+                    self.compression.clone(),
+                    self.connector.clone(),
+                );
+
+                shard
+                    .with_shard_info(Some(ShardInfo {
+                        id,
+                        count: self.shard_count,
+                    }))
+                    .with_intents(self.intents)
+                    .with_identify_queue(self.identify_queue.clone())
+                    .with_version(self.version)
+                    .with_presence(self.presence.clone())
+                    .with_session(self.session(id).cloned());
+                shard
+            })
+            .collect();
+
+        Ok(shards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn manager(shard_count: u64) -> ShardManager {
+        let gateway = serde_json::from_value(json!({
+            "url": "wss://gateway.discord.gg",
+            "shards": shard_count,
+            "session_start_limit": {
+                "total": 1000,
+                "remaining": 1000,
+                "reset_after": 0,
+                "max_concurrency": 1,
+            },
+        }))
+        .unwrap();
+
+        ShardManager::default_with(gateway, "token".to_owned())
+    }
+
+    fn session(id: u64, count: u64) -> SessionInfo {
+        SessionInfo {
+            session_id: format!("session {}", id),
+            last_seqnum: 1,
+            resume_url: "wss://gateway.discord.gg".to_owned(),
+            shard_info: Some(ShardInfo { id, count }),
+        }
+    }
+
+    #[tokio::test]
+    async fn matches_sessions_to_final_shard_count() {
+        let mut manager = manager(4);
+        manager
+            .with_sessions((0..4).map(|id| session(id, 4)).chain(Some(session(0, 2))))
+            .with_shard_count(2);
+
+        let shards = manager.spawn().await.unwrap();
+
+        assert_eq!(shards.len(), 2);
+        assert_eq!(shards[0].session().unwrap().session_id, "session 0");
+        assert!(shards[1].session().is_none());
+    }
+}
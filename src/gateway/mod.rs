@@ -1,11 +1,54 @@
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr as DeserializeRepr, Serialize_repr as SerializeRepr};
+
+pub mod etf;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 
 mod connector;
+mod error;
+mod handle;
+mod identify;
+mod inflater;
+mod intents;
+mod latency;
+mod manager;
+mod ratelimit;
 mod shard;
 
 pub use connector::*;
+pub use error::*;
+pub use handle::ShardHandle;
+pub use identify::*;
+pub use intents::*;
+pub use latency::*;
+pub use manager::*;
 pub use shard::*;
 
+/// The version of the gateway API to connect with.
+///
+/// Models accept the representations used by all supported versions: role
+/// permissions are sent as integers in v6 and as strings since v8, and fields
+/// which are no longer sent (such as a guild's region since v10) are optional.
+/// Identify properties are sent with the keys expected by the version. Since
+/// v10, message content is only sent with the `MESSAGE_CONTENT` intent.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, DeserializeRepr, SerializeRepr,
+)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum GatewayVersion {
+    V6 = 6,
+    V8 = 8,
+    V10 = 10,
+}
+
+impl Default for GatewayVersion {
+    fn default() -> Self {
+        Self::V8
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[non_exhaustive]
 pub enum PayloadEncoding {
@@ -64,7 +107,7 @@ impl Default for TransportCompression {
 #[non_exhaustive]
 pub struct GatewayConnectionParams {
     #[serde(rename = "v")]
-    version: u8,
+    version: GatewayVersion,
 
     encoding: PayloadEncoding,
 
@@ -72,3 +115,13 @@ pub struct GatewayConnectionParams {
     #[serde(default)]
     compression: Option<TransportCompression>,
 }
+
+impl GatewayConnectionParams {
+    pub fn version(&self) -> GatewayVersion {
+        self.version
+    }
+
+    pub fn encoding(&self) -> &PayloadEncoding {
+        &self.encoding
+    }
+}
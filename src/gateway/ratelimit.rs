@@ -0,0 +1,134 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The number of payloads that may be sent per [`COMMAND_PERIOD`].
+const COMMAND_LIMIT: usize = 120;
+
+const COMMAND_PERIOD: Duration = Duration::from_secs(60);
+
+/// Limits the payloads a shard sends per connection, by keeping track of
+/// when each payload was sent during the last [`COMMAND_PERIOD`].
+///
+/// Part of the capacity is reserved for heartbeats, which are never delayed,
+/// so that other commands can't starve them. Heartbeats still count against
+/// the limit.
+#[derive(Debug)]
+pub(crate) struct CommandRatelimiter {
+    sent: VecDeque<Instant>,
+    reserved: usize,
+}
+
+impl CommandRatelimiter {
+    /// Forgets payloads sent longer than a period ago.
+    fn prune(&mut self, now: Instant) {
+        while let Some(&sent_at) = self.sent.front() {
+            if now.duration_since(sent_at) < COMMAND_PERIOD {
+                break;
+            }
+
+            self.sent.pop_front();
+        }
+    }
+
+    /// Restores the full capacity, since the limit applies per connection.
+    pub(crate) fn reset(&mut self) {
+        self.sent.clear();
+    }
+
+    /// Reserves enough capacity for the heartbeats sent during one period at
+    /// the given interval, plus one for heartbeats requested by the gateway.
+    pub(crate) fn set_heartbeat_interval(&mut self, interval: Duration) {
+        let heartbeats = COMMAND_PERIOD.as_secs_f64() / interval.as_secs_f64().max(1.0);
+
+        self.reserved = (heartbeats.ceil() as usize + 1).min(COMMAND_LIMIT - 1);
+    }
+
+    /// The time until a command can be sent.
+    pub(crate) fn delay(&mut self) -> Duration {
+        let now = Instant::now();
+        self.prune(now);
+
+        let available = COMMAND_LIMIT - self.reserved;
+
+        if self.sent.len() < available {
+            return Duration::from_secs(0);
+        }
+
+        // Enough payloads must expire for the number sent to drop below the
+        // available capacity.
+        let sent_at = self.sent[self.sent.len() - available];
+
+        (sent_at + COMMAND_PERIOD).saturating_duration_since(now)
+    }
+
+    /// Takes capacity for a command, or returns the time until it is
+    /// available.
+    pub(crate) fn try_acquire(&mut self) -> Result<(), Duration> {
+        let delay = self.delay();
+
+        if delay > Duration::from_secs(0) {
+            return Err(delay);
+        }
+
+        self.sent.push_back(Instant::now());
+        Ok(())
+    }
+
+    /// Takes capacity for a heartbeat, which is always available.
+    pub(crate) fn acquire_heartbeat(&mut self) {
+        let now = Instant::now();
+        self.prune(now);
+
+        self.sent.push_back(now);
+    }
+}
+
+impl Default for CommandRatelimiter {
+    fn default() -> Self {
+        Self {
+            sent: VecDeque::with_capacity(COMMAND_LIMIT),
+            // Hello hasn't been received yet, so reserve capacity for heartbeats at the
+            // usual interval of about 41 seconds.
+            reserved: 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_exceeds_limit_within_period() {
+        let mut ratelimiter = CommandRatelimiter::default();
+        ratelimiter.set_heartbeat_interval(Duration::from_millis(41_250));
+
+        let mut commands = 0;
+        while ratelimiter.try_acquire().is_ok() {
+            commands += 1;
+        }
+
+        for _ in 0..ratelimiter.reserved {
+            ratelimiter.acquire_heartbeat();
+        }
+
+        assert_eq!(commands, COMMAND_LIMIT - ratelimiter.reserved);
+        assert_eq!(ratelimiter.sent.len(), COMMAND_LIMIT);
+
+        let delay = ratelimiter.delay();
+        assert!(delay > COMMAND_PERIOD - Duration::from_secs(1));
+        assert!(delay <= COMMAND_PERIOD);
+    }
+
+    #[test]
+    fn reset_restores_capacity() {
+        let mut ratelimiter = CommandRatelimiter::default();
+
+        while ratelimiter.try_acquire().is_ok() {}
+        ratelimiter.reset();
+
+        assert_eq!(ratelimiter.delay(), Duration::from_secs(0));
+    }
+}
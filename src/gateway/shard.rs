@@ -1,80 +1,46 @@
 use std::{
-    borrow::Cow,
     env::consts,
+    future::Future,
+    io::Read,
+    sync::{atomic::Ordering, Arc},
     time::{Duration, Instant},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use async_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as WsMessage};
-use futures::{SinkExt, StreamExt};
-use log::{debug, trace};
-use regex::bytes::Regex;
+use flate2::read::ZlibDecoder;
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver},
+    future, StreamExt,
+};
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
 use serde_json;
 use tokio::time::{sleep, timeout, timeout_at};
 
 use super::{
-    GatewayCompression, GatewayConnectionParams, GatewayConnector, GatewayStream,
-    PayloadCompression, PayloadEncoding, WsGatewayConnector,
+    connector::{CloseFrame, Frame, GatewayTransport},
+    etf,
+    handle::{CloseRequest, ShardCommand, ShardHandle},
+    inflater::Inflater,
+    GatewayCloseCode, GatewayCompression, GatewayConnectionParams, GatewayConnector, GatewayError,
+    GatewayVersion, IdentifyPermit, IdentifyQueue, Intents, LocalIdentifyQueue, PayloadCompression,
+    PayloadEncoding, ShardLatency, TransportCompression, WsGatewayConnector,
 };
 
 use crate::{
-    events::{payload::*, PayloadDelegate},
+    events::{dispatch::DispatchEvent, payload::*, PayloadDelegate},
     models::Gateway,
     util::{AsyncSink, AsyncStream, NoneError},
 };
 
-#[derive(Clone, Copy, Debug)]
-#[non_exhaustive]
-enum GatewayCloseCode {
-    UnknownError,
-    UnknownOpcode,
-    DecodeError,
-    NotAuthenticated,
-    AuthenticationFailed,
-    AlreadyAuthenticated,
-    InvalidSeqnum,
-    Ratelimited,
-    SessionTimeout,
-    InvalidShard,
-    ShardingRequired,
-    InvalidVersion,
-    InvalidIntents,
-    UnauthorizedIntents,
-    Unknown,
-}
-
-impl From<u16> for GatewayCloseCode {
-    fn from(code: u16) -> Self {
-        match code {
-            4000 => GatewayCloseCode::UnknownError,
-            4001 => GatewayCloseCode::UnknownOpcode,
-            4002 => GatewayCloseCode::DecodeError,
-            4003 => GatewayCloseCode::NotAuthenticated,
-            4004 => GatewayCloseCode::AuthenticationFailed,
-            4005 => GatewayCloseCode::AlreadyAuthenticated,
-            4007 => GatewayCloseCode::InvalidSeqnum,
-            4008 => GatewayCloseCode::Ratelimited,
-            4009 => GatewayCloseCode::SessionTimeout,
-            4010 => GatewayCloseCode::InvalidShard,
-            4011 => GatewayCloseCode::ShardingRequired,
-            4012 => GatewayCloseCode::InvalidVersion,
-            4013 => GatewayCloseCode::InvalidIntents,
-            4014 => GatewayCloseCode::UnauthorizedIntents,
-
-            // Fallback case
-            #[allow(overlapping_patterns)]
-            4000..=4999 => GatewayCloseCode::Unknown,
-
-            _ => panic!("Invalid gateway close code {:?}", code),
-        }
-    }
-}
-
 pub enum ConnectionState<C: GatewayConnector> {
     Disconnected,
     Connecting,
     Connected(GatewayState<C>),
+
+    /// The shard was closed and won't reconnect.
+    Closed,
 }
 
 impl<C: GatewayConnector> Default for ConnectionState<C> {
@@ -84,25 +50,76 @@ impl<C: GatewayConnector> Default for ConnectionState<C> {
 }
 
 pub struct GatewayState<C: GatewayConnector> {
-    connection: GatewayStream<C::Input, C::Output>,
-    last_seqnum: Option<u64>,
+    connection: C::Transport,
+    status: ShardStatus,
     heartbeat_interval: Option<Duration>,
+    next_heartbeat: Option<Instant>,
     last_heartbeat: Instant,
     last_heartbeat_ack: Instant,
+    is_awaiting_heartbeat_ack: bool,
+    inflater: Option<Inflater>,
 }
 
 impl<C: GatewayConnector> GatewayState<C> {
-    fn new_from_connection(connection: GatewayStream<C::Input, C::Output>) -> Self {
+    fn new_from_connection(connection: C::Transport, inflater: Option<Inflater>) -> Self {
         Self {
             connection,
-            last_seqnum: Default::default(),
+            status: ShardStatus::Connecting,
             heartbeat_interval: Default::default(),
+            next_heartbeat: Default::default(),
             last_heartbeat: Instant::now(),
             last_heartbeat_ack: Instant::now(),
+            is_awaiting_heartbeat_ack: false,
+            inflater,
         }
     }
 }
 
+/// The status of a shard's connection to the gateway.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShardStatus {
+    /// The shard is not connected to the gateway.
+    Disconnected,
+
+    /// The shard is connected to the gateway, but has not yet started or
+    /// resumed a session.
+    Connecting,
+
+    /// The shard is starting a new session.
+    Identifying,
+
+    /// The shard is resuming a previous session.
+    Resuming,
+
+    /// The shard's session is active and events are being received.
+    Connected,
+}
+
+/// Data necessary for resuming a gateway session after reconnecting.
+///
+/// This can be persisted to resume the session from another process, e.g.
+/// after a restart, using [`Shard::with_session`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub last_seqnum: u64,
+
+    /// The URL of the gateway to which the session's shard was connected.
+    pub resume_url: String,
+
+    #[serde(default)]
+    pub shard_info: Option<ShardInfo>,
+}
+
+/// The next item received by a shard while it is being polled.
+enum Next {
+    Message(Option<Result<Frame>>),
+    Command(ShardCommand),
+    Close(CloseRequest),
+}
+
 enum ConnectError<E> {
     ShouldReconnect,
     ShouldAbort(E),
@@ -111,11 +128,23 @@ enum ConnectError<E> {
 pub struct Shard<C: GatewayConnector + Send> {
     gateway: Gateway,
     token: String,
+    shard_info: Option<ShardInfo>,
+    intents: Intents,
+    version: GatewayVersion,
     encoding: PayloadEncoding,
     compression: GatewayCompression,
 
     connector: C,
+    identify_queue: Arc<dyn IdentifyQueue>,
+    identify_permit: Option<IdentifyPermit>,
+    presence: Option<UpdatePresence>,
+
     state: ConnectionState<C>,
+    session: Option<SessionInfo>,
+
+    handle: ShardHandle,
+    commands: UnboundedReceiver<ShardCommand>,
+    close_requests: UnboundedReceiver<CloseRequest>,
 }
 
 impl Shard<WsGatewayConnector> {
@@ -128,11 +157,39 @@ impl Shard<WsGatewayConnector> {
             WsGatewayConnector,
         )
     }
+
+    /// Creates a shard which resumes the given session, connecting to the
+    /// gateway to which it belongs.
+    ///
+    /// The intents, encoding and compression should be the ones with which the
+    /// session was started, since they also apply when the shard has to start
+    /// a new session.
+    pub fn resume_with(
+        session: SessionInfo,
+        token: String,
+        intents: Intents,
+        encoding: PayloadEncoding,
+        compression: GatewayCompression,
+    ) -> Self {
+        let mut shard = Self::new(
+            Gateway::new(session.resume_url.clone()),
+            token,
+            encoding,
+            compression,
+            WsGatewayConnector,
+        );
+
+        shard
+            .with_shard_info(session.shard_info)
+            .with_intents(intents)
+            .with_session(Some(session));
+        shard
+    }
 }
 
 impl<C: GatewayConnector + Send + Sync> Shard<C> {
-    const GATEWAY_VERSION: u8 = 6;
-
+    /// Creates an unsharded shard, which identifies with the non-privileged
+    /// intents.
     pub fn new(
         gateway: Gateway,
         token: String,
@@ -140,19 +197,112 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
         compression: GatewayCompression,
         connector: C,
     ) -> Self {
+        let (command_tx, commands) = mpsc::unbounded();
+        let (close_tx, close_requests) = mpsc::unbounded();
+
         Self {
+            handle: ShardHandle::new(None, command_tx, close_tx),
+            commands,
+            close_requests,
             gateway,
             token,
+            shard_info: None,
+            intents: Intents::non_privileged(),
             encoding,
             compression,
             connector,
+            identify_queue: Arc::new(LocalIdentifyQueue::default()),
+            identify_permit: None,
+            version: Default::default(),
+            presence: None,
             state: Default::default(),
+            session: Default::default(),
+        }
+    }
+
+    /// The ID of the shard and the total number of shards, if the bot is
+    /// sharded.
+    pub fn shard_info(&self) -> Option<ShardInfo> {
+        self.shard_info
+    }
+
+    /// Sets the ID of the shard and the total number of shards.
+    ///
+    /// Handles which were taken before this is called keep the previous
+    /// shard info.
+    pub fn with_shard_info(&mut self, shard_info: Option<ShardInfo>) -> &mut Self {
+        self.shard_info = shard_info;
+        self.handle.with_shard_info(shard_info);
+        self
+    }
+
+    /// Sets the queue through which identifying is ratelimited, which should
+    /// be shared by all shards of the bot.
+    pub fn with_identify_queue(&mut self, identify_queue: Arc<dyn IdentifyQueue>) -> &mut Self {
+        self.identify_queue = identify_queue;
+        self
+    }
+
+    /// Sets the presence with which the shard identifies.
+    pub fn with_presence(&mut self, presence: Option<UpdatePresence>) -> &mut Self {
+        self.presence = presence;
+        self
+    }
+
+    /// Statistics on the round-trip times of the shard's heartbeats.
+    pub fn latency(&self) -> ShardLatency {
+        self.handle.latency()
+    }
+
+    /// Sets the version of the gateway API to connect with.
+    pub fn with_version(&mut self, version: GatewayVersion) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets a session to resume when connecting, instead of identifying.
+    ///
+    /// The session must have been started with the same shard info and
+    /// intents.
+    pub fn with_session(&mut self, session: Option<SessionInfo>) -> &mut Self {
+        self.session = session;
+        self
+    }
+
+    /// The current session, which can be persisted and resumed later using
+    /// [`with_session`](Self::with_session).
+    pub fn session(&self) -> Option<&SessionInfo> {
+        self.session.as_ref()
+    }
+
+    /// A handle through which payloads can be sent while the shard is being
+    /// polled.
+    pub fn handle(&self) -> ShardHandle {
+        self.handle.clone()
+    }
+
+    /// The intents with which the shard identifies.
+    pub fn intents(&self) -> Intents {
+        self.intents
+    }
+
+    /// Sets the intents with which the shard identifies.
+    pub fn with_intents(&mut self, intents: Intents) -> &mut Self {
+        self.intents = intents;
+        self
+    }
+
+    /// The current status of the shard's connection to the gateway.
+    pub fn status(&self) -> ShardStatus {
+        match &self.state {
+            ConnectionState::Connected(state) => state.status,
+            _ => ShardStatus::Disconnected,
         }
     }
 
     pub fn conn_params(&self) -> GatewayConnectionParams {
         GatewayConnectionParams {
-            version: Self::GATEWAY_VERSION,
+            version: self.version,
             encoding: self.encoding.clone(),
             compression: match &self.compression {
                 GatewayCompression::Transport(ty) => Some(ty.clone()),
@@ -161,59 +311,99 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
         }
     }
 
-    // FIXME: This is a hack. This should be removed as soon as `serde` has support
-    // for integer/bool tag renaming.
     #[inline]
-    fn deserialize_workaround_json(bytes: &[u8]) -> Cow<[u8]> {
-        Regex::new(r#""(op|unavailable|type)":\s*(\d+|true|false)"#)
-            .unwrap()
-            .replace_all(bytes, "\"$1\":\"$2\"".as_bytes())
+    fn decode_bytes(&self, bytes: &[u8]) -> Result<Payload> {
+        match self.encoding {
+            PayloadEncoding::Json => Ok(serde_json::from_slice(bytes)?),
+            PayloadEncoding::Etf => Ok(etf::from_slice(bytes)?),
+        }
     }
 
-    // FIXME: This is a hack. This should be removed as soon as `serde` has support
-    // for integer/bool tag renaming.
+    /// Decompresses a binary message which was compressed using payload
+    /// compression, in which case each message is a complete zlib stream.
     #[inline]
-    fn serialize_workaround_json(bytes: &[u8]) -> Cow<[u8]> {
-        Regex::new(r#""(op|unavailable|type)":\s*"(\d+|true|false)""#)
-            .unwrap()
-            .replace_all(bytes, "\"$1\":$2".as_bytes())
+    fn decompress_bytes(bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(bytes.len() * 4);
+        ZlibDecoder::new(bytes).read_to_end(&mut output)?;
+
+        Ok(output)
     }
 
+    /// Whether a message starts with a zlib header, which is the case for
+    /// messages sent with payload compression. Uncompressed ETF messages start
+    /// with the format version instead.
     #[inline]
-    fn decode_bytes(&self, bytes: &[u8]) -> Result<Payload> {
-        match self.encoding {
-            PayloadEncoding::Json => Ok(serde_json::from_slice(
-                Self::deserialize_workaround_json(bytes).as_ref(),
-            )?),
-            PayloadEncoding::Etf => unimplemented!(),
+    fn is_zlib(bytes: &[u8]) -> bool {
+        match bytes {
+            // The compression method must be deflate, and the header a multiple of 31.
+            [cmf, flg, ..] => cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16) % 31 == 0,
+            _ => false,
         }
     }
 
+    /// Decompresses a binary message according to the shard's compression
+    /// mode, or returns it as is if it wasn't compressed (e.g. ETF messages
+    /// without compression). Returns `None` if the message is split across
+    /// multiple frames and has not been fully received yet.
+    ///
+    /// If a message sent over a zlib-stream connection can't be inflated, the
+    /// shard is disconnected and a [`NoneError`] is returned.
     #[inline]
-    async fn _decompress_bytes(&self, _bytes: &[u8]) -> Vec<u8> {
-        todo!()
+    fn inflate_bytes(&mut self, bytes: Vec<u8>) -> Result<Option<Vec<u8>>> {
+        let inflater = match (&self.compression, &mut self.state) {
+            (GatewayCompression::None, _) => return Ok(Some(bytes)),
+            (GatewayCompression::Payload(PayloadCompression::Zlib), _) => {
+                if !Self::is_zlib(&bytes) {
+                    return Ok(Some(bytes));
+                }
+
+                return Self::decompress_bytes(&bytes).map(Some);
+            }
+            (
+                GatewayCompression::Transport(TransportCompression::ZlibStream),
+                ConnectionState::Connected(GatewayState {
+                    inflater: Some(inflater),
+                    ..
+                }),
+            ) => inflater,
+            _ => return Err(anyhow!("Received a binary message while disconnected")),
+        };
+
+        match inflater.push(&bytes) {
+            Ok(message) => Ok(message),
+            Err(err) => {
+                // The zlib context can't recover from a corrupt message, so reconnect (and
+                // attempt to resume) with a new one on the next poll.
+                warn!("[Shard] Failed to inflate message {:?}, reconnecting", err);
+                self.disconnect();
+                Err(NoneError.into())
+            }
+        }
     }
 
+    /// Deserializes a received message. Returns `None` if the message is
+    /// incomplete and more frames are needed to deserialize it.
     #[inline]
-    async fn deserialize_message(&self, message: WsMessage) -> Result<Payload> {
-        trace!("[Shard] Deserializing message {:?}", message);
+    fn deserialize_message(&mut self, frame: Frame) -> Result<Option<Payload>> {
+        trace!("[Shard] Deserializing frame {:?}", frame);
 
-        match message {
-            WsMessage::Binary(_bytes) => unimplemented!(),
-            WsMessage::Text(string) => self.decode_bytes(string.as_bytes()),
-            _ => unreachable!(),
+        match frame {
+            Frame::Binary(bytes) => match self.inflate_bytes(bytes)? {
+                Some(bytes) => self.decode_bytes(&bytes).map(Some),
+                None => Ok(None),
+            },
+            Frame::Text(string) => self.decode_bytes(string.as_bytes()).map(Some),
+            Frame::Close(_) => unreachable!(),
         }
     }
 
     #[inline]
-    fn serialize_payload(&self, payload: Payload) -> Result<WsMessage> {
+    fn serialize_payload(&self, payload: Payload) -> Result<Frame> {
         trace!("[Shard] Serializing payload {:?}", payload);
 
         match self.encoding {
-            PayloadEncoding::Json => Ok(WsMessage::Text(String::from_utf8(
-                Self::serialize_workaround_json(&serde_json::to_vec(&payload)?).into(),
-            )?)),
-            PayloadEncoding::Etf => unimplemented!(),
+            PayloadEncoding::Json => Ok(Frame::Text(serde_json::to_string(&payload)?)),
+            PayloadEncoding::Etf => Ok(Frame::Binary(etf::to_vec(&payload)?)),
         }
     }
 
@@ -226,20 +416,39 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
         Duration::from_secs(5 * 2u64.pow(conn_attempts.min(6)))
     }
 
+    /// The gateway to connect to, which is the one to which the session
+    /// belongs if there is a session to resume.
+    fn connect_gateway(&self) -> Gateway {
+        match &self.session {
+            Some(session) => Gateway::new(session.resume_url.clone()),
+            None => self.gateway.clone(),
+        }
+    }
+
     async fn connect(
-        &mut self,
-    ) -> Result<GatewayStream<C::Input, C::Output>, ConnectError<anyhow::Error>> {
+        connector: &C,
+        gateway: Gateway,
+        conn_params: GatewayConnectionParams,
+    ) -> Result<C::Transport, ConnectError<anyhow::Error>> {
         let timeout_res = timeout(
             Duration::from_secs(5),
-            self.connector
-                .connect(self.gateway.clone(), self.conn_params()),
+            connector.connect(gateway, conn_params),
         )
         .await;
 
         match timeout_res {
             Ok(conn_res) => match conn_res {
                 Ok(conn) => Ok(conn),
-                Err(conn_err) => Err(ConnectError::ShouldAbort(conn_err)),
+                Err(conn_err) => match conn_err.downcast_ref::<GatewayError>() {
+                    // Configuration errors would only reoccur when retrying
+                    Some(gateway_err) if !gateway_err.is_recoverable() => {
+                        Err(ConnectError::ShouldAbort(conn_err))
+                    }
+                    _ => {
+                        debug!("[Shard] Failed to connect {:?}, retrying", conn_err);
+                        Err(ConnectError::ShouldReconnect)
+                    }
+                },
             },
             // Timeout error
             Err(_) => Err(ConnectError::ShouldReconnect),
@@ -247,24 +456,71 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
     }
 
     async fn ensure_connected(&mut self) -> Result<&mut GatewayState<C>> {
+        // If the previous heartbeat was never acknowledged by the time the next one is
+        // due, the connection is most likely dead (a "zombie" connection) and should be
+        // replaced.
+        if let ConnectionState::Connected(state) = &self.state {
+            if Self::should_heartbeat(state) && state.is_awaiting_heartbeat_ack {
+                warn!("[Shard] Heartbeat was not acknowledged, reconnecting");
+                self.reconnect().await;
+            }
+        }
+
+        if let ConnectionState::Closed = self.state {
+            return Err(anyhow!("Shard has been closed"));
+        }
+
         if let ConnectionState::Disconnected = self.state {
+            // The identify queue is waited on before connecting rather than once Hello
+            // is received, since the connection would neither be read nor heartbeated
+            // while waiting, and the gateway would drop it if the wait is long. The
+            // permit is kept across failed connection attempts until it is used.
+            if self.session.is_none() && self.identify_permit.is_none() {
+                self.identify_permit = Some(self.wait_for_identify().await?);
+            }
+
             let mut conn_attempts = 0u32;
 
+            // Connecting may take a while, so close requests are handled in the meantime.
             let connection = loop {
-                match self.connect().await {
-                    Ok(conn) => break conn,
-                    Err(conn_err) => match conn_err {
-                        ConnectError::ShouldReconnect => {
-                            sleep(Self::compute_connect_delay(conn_attempts)).await
+                let connect =
+                    Self::connect(&self.connector, self.connect_gateway(), self.conn_params());
+
+                let conn_err =
+                    match Self::until_close_requested(&mut self.close_requests, connect).await {
+                        Ok(Ok(conn)) => break conn,
+                        Ok(Err(conn_err)) => conn_err,
+                        Err(request) => return self.handle_close_request(request).await,
+                    };
+
+                match conn_err {
+                    ConnectError::ShouldReconnect => {
+                        let delay = sleep(Self::compute_connect_delay(conn_attempts));
+
+                        if let Err(request) =
+                            Self::until_close_requested(&mut self.close_requests, delay).await
+                        {
+                            return self.handle_close_request(request).await;
                         }
-                        ConnectError::ShouldAbort(err) => return Err(err),
-                    },
+                    }
+                    ConnectError::ShouldAbort(err) => return Err(err),
                 }
 
                 conn_attempts += 1;
             };
 
-            let state = GatewayState::new_from_connection(connection);
+            // Each connection uses its own zlib context, so the inflater must be replaced
+            // whenever the shard reconnects.
+            let inflater = match self.compression {
+                GatewayCompression::Transport(TransportCompression::ZlibStream) => {
+                    Some(Inflater::new())
+                }
+                _ => None,
+            };
+
+            let state = GatewayState::new_from_connection(connection, inflater);
+
+            self.handle.shared().ratelimiter().reset();
 
             self.state = ConnectionState::Connected(state);
         }
@@ -282,71 +538,253 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
         panic!("Expected to be connected");
     }
 
+    /// Drops the current connection, if any. The session is kept so that it
+    /// can be resumed once the shard reconnects.
+    fn disconnect(&mut self) {
+        self.state = ConnectionState::Disconnected;
+    }
+
+    /// Drops the current connection, if any, and ends the shard's stream.
+    ///
+    /// Close requests which are still pending are answered without a session,
+    /// and handles can no longer send new ones.
+    fn mark_closed(&mut self) {
+        self.state = ConnectionState::Closed;
+        self.handle
+            .shared()
+            .is_closed
+            .store(true, Ordering::Release);
+
+        self.close_requests.close();
+
+        while let Ok(Some(request)) = self.close_requests.try_next() {
+            // The requester may have stopped waiting, which is not an issue.
+            let _ = request.reply.send(None);
+        }
+    }
+
+    /// Waits for `future` to complete, unless a close request is received
+    /// first, in which case it is returned instead.
+    async fn until_close_requested<F: Future>(
+        close_requests: &mut UnboundedReceiver<CloseRequest>,
+        future: F,
+    ) -> Result<F::Output, CloseRequest> {
+        tokio::select! {
+            output = future => Ok(output),
+            Some(request) = close_requests.next() => Err(request),
+        }
+    }
+
+    /// Shuts down the shard as requested through a handle. Returns a
+    /// [`NoneError`], so that the stream ends on the next poll.
+    async fn handle_close_request<T>(&mut self, request: CloseRequest) -> Result<T> {
+        let session = self.shutdown(request.is_resumable).await;
+
+        // The requester may have stopped waiting, which is not an issue.
+        let _ = request.reply.send(session);
+
+        Err(NoneError.into())
+    }
+
+    /// Closes the current connection, if any, with a non-1000 close code so
+    /// that the session can be resumed once the shard reconnects.
+    async fn reconnect(&mut self) {
+        self.send_close_frame(4000).await;
+        self.disconnect();
+    }
+
+    /// Sends a close frame over the current connection, if any. Close codes
+    /// 1000 and 1001 invalidate the session, while all others keep it
+    /// resumable.
+    async fn send_close_frame(&mut self, code: u16) {
+        if let ConnectionState::Connected(state) = &mut self.state {
+            let frame = CloseFrame {
+                code,
+                reason: String::new(),
+            };
+
+            // The connection is being dropped regardless, so a failure to close it cleanly
+            // is not an issue.
+            if let Err(err) = state.connection.send(Frame::Close(Some(frame))).await {
+                debug!("[Shard] Failed to close connection {:?}", err);
+            }
+        }
+    }
+
+    /// Closes the connection, after which the shard's stream ends.
+    ///
+    /// If `is_resumable` is set, the session is kept open on the gateway's side
+    /// and its info is returned, so that it can be resumed by another shard
+    /// (e.g. after a restart). Otherwise, the session is ended.
+    pub async fn shutdown(&mut self, is_resumable: bool) -> Option<SessionInfo> {
+        debug!("[Shard] Shutting down {{ resumable: {:?} }}", is_resumable);
+
+        let code = if is_resumable { 4000 } else { 1000 };
+
+        self.send_close_frame(code).await;
+        self.mark_closed();
+
+        let session = self.session.take();
+
+        if is_resumable {
+            session
+        } else {
+            None
+        }
+    }
+
     async fn fetch_next(
         &mut self,
     ) -> Result<<Self as AsyncStream>::Item, <Self as AsyncStream>::Error> {
-        let state = self.ensure_connected().await?;
+        self.ensure_connected().await?;
 
-        let next_heartbeat_time = Self::next_heartbeat_time(state);
-
-        // If a heartbeat interval is set, only poll until the next time at
-        // which a heartbeat should be sent. If the timeout is hit, the
-        // connection-polling future will be cancelled and the resulting timeout error
-        // will be mapped into a NoneError, allowing connection-maintaining activities
-        // to be resumed before polling again.
-        let message = if let Some(next_heartbeat_time) = next_heartbeat_time {
-            timeout_at(next_heartbeat_time.into(), state.connection.next())
-                .await
-                // Map the timeout error into a NoneError
-                .map_err(|_| NoneError)?
-        } else {
-            state.connection.next().await
-        }
-        .ok_or(NoneError)??;
+        let next = {
+            let state = match &mut self.state {
+                ConnectionState::Connected(state) => state,
+                _ => unreachable!(),
+            };
+            let commands = &mut self.commands;
+            let close_requests = &mut self.close_requests;
+
+            let next_heartbeat_time = Self::next_heartbeat_time(state);
+
+            // Commands are only sent once the session is active, since the gateway
+            // rejects most payloads before that.
+            let is_session_active = state.status == ShardStatus::Connected;
+
+            // Commands are only received once they can be sent without exceeding the rate
+            // limit, so that waiting for capacity doesn't block the connection.
+            let command_delay = self.handle.shared().ratelimiter().delay();
+
+            // If a heartbeat interval is set, only poll until the next time at
+            // which a heartbeat should be sent. If the timeout is hit, the
+            // connection-polling future will be cancelled and the resulting timeout error
+            // will be mapped into a NoneError, allowing connection-maintaining activities
+            // to be resumed before polling again.
+            let next_message = async move {
+                if let Some(next_heartbeat_time) = next_heartbeat_time {
+                    timeout_at(next_heartbeat_time.into(), state.connection.receive())
+                        .await
+                        // Map the timeout error into a NoneError
+                        .map_err(|_| NoneError)
+                } else {
+                    Ok(state.connection.receive().await)
+                }
+            };
+
+            let next_command = async move {
+                if is_session_active {
+                    sleep(command_delay).await;
+                    commands.next().await
+                } else {
+                    future::pending().await
+                }
+            };
+
+            tokio::select! {
+                message = next_message => Next::Message(message?),
+                Some(command) = next_command => Next::Command(command),
+                Some(request) = close_requests.next() => Next::Close(request),
+            }
+        };
+
+        let message = match next {
+            Next::Message(message) => message,
+            Next::Command(command) => {
+                self.handle_command(command).await?;
+                return Err(NoneError.into());
+            }
+            Next::Close(request) => return self.handle_close_request(request).await,
+        };
+
+        // If the connection was dropped, reconnect (and attempt to resume) on the next
+        // poll.
+        let message = match message {
+            Some(Ok(message)) => message,
+            Some(Err(err)) => {
+                debug!("[Shard] Connection error {:?}, reconnecting", err);
+                self.disconnect();
+                return Err(NoneError.into());
+            }
+            None => {
+                debug!("[Shard] Connection closed, reconnecting");
+                self.disconnect();
+                return Err(NoneError.into());
+            }
+        };
 
         match message {
-            WsMessage::Close(frame_opt) => match frame_opt {
-                Some(frame) => match frame.code {
-                    CloseCode::Library(code) => match code.into() {
-                        // Close codes which can be recovered from
-                        // TODO: Handle these more specifically (e.g. resume/reconnect)
-                        GatewayCloseCode::UnknownError
-                        | GatewayCloseCode::InvalidSeqnum
-                        | GatewayCloseCode::Ratelimited
-                        | GatewayCloseCode::SessionTimeout => return Err(NoneError.into()),
-
-                        // Close codes which represent a fault on the side of the library and are
-                        // likely unrecoverable
-                        GatewayCloseCode::UnknownOpcode
-                        | GatewayCloseCode::DecodeError
-                        | GatewayCloseCode::NotAuthenticated
-                        | GatewayCloseCode::AlreadyAuthenticated
-                        | GatewayCloseCode::InvalidShard
-                        | GatewayCloseCode::InvalidVersion
-                        | GatewayCloseCode::InvalidIntents => todo!(), // TODO: return error
-
-                        // Close codes which represent a fault on the side of the end-user and are
-                        // unrecoverable
-                        GatewayCloseCode::AuthenticationFailed
-                        | GatewayCloseCode::ShardingRequired
-                        | GatewayCloseCode::UnauthorizedIntents => todo!(), // TODO: return error
-
-                        GatewayCloseCode::Unknown => {
-                            debug!("[Shard] Unknown gateway close code {:?}", code);
-                            return Err(NoneError.into());
-                        }
+            Frame::Close(frame_opt) => {
+                debug!("[Shard] Received close frame {:?}", frame_opt);
+                self.disconnect();
+
+                match frame_opt {
+                    Some(frame) => match frame.code {
+                        code @ 4000..=4999 => match GatewayCloseCode::from(code) {
+                            // Close codes which can be recovered from by resuming
+                            GatewayCloseCode::UnknownError | GatewayCloseCode::Ratelimited => {
+                                return Err(NoneError.into())
+                            }
+
+                            // Close codes which can be recovered from, but only with a new session
+                            GatewayCloseCode::InvalidSeqnum | GatewayCloseCode::SessionTimeout => {
+                                self.session = None;
+                                return Err(NoneError.into());
+                            }
+
+                            // Close codes which represent a fault on the side of the library, but
+                            // can be recovered from with a new session
+                            close_code @ GatewayCloseCode::UnknownOpcode
+                            | close_code @ GatewayCloseCode::DecodeError
+                            | close_code @ GatewayCloseCode::NotAuthenticated
+                            | close_code @ GatewayCloseCode::AlreadyAuthenticated => {
+                                self.session = None;
+
+                                return Err(GatewayError::Closed {
+                                    code: close_code,
+                                    reason: frame.reason,
+                                    is_recoverable: true,
+                                }
+                                .into());
+                            }
+
+                            // Close codes which represent a fault in the configuration of the
+                            // library or on the side of the end-user and are unrecoverable, so the
+                            // shard is closed rather than reconnecting with the same configuration
+                            close_code @ GatewayCloseCode::AuthenticationFailed
+                            | close_code @ GatewayCloseCode::InvalidShard
+                            | close_code @ GatewayCloseCode::ShardingRequired
+                            | close_code @ GatewayCloseCode::InvalidVersion
+                            | close_code @ GatewayCloseCode::InvalidIntents
+                            | close_code @ GatewayCloseCode::UnauthorizedIntents => {
+                                self.session = None;
+                                self.mark_closed();
+
+                                return Err(GatewayError::Closed {
+                                    code: close_code,
+                                    reason: frame.reason,
+                                    is_recoverable: false,
+                                }
+                                .into());
+                            }
+
+                            GatewayCloseCode::Unknown => {
+                                debug!("[Shard] Unknown gateway close code {:?}", code);
+                                return Err(NoneError.into());
+                            }
+                        },
+                        // TODO: For now, it seems like all/most standard close codes should be
+                        // recoverable and thus the shard should reconnect. This should be
+                        // reevaluated later.
+                        _ => return Err(NoneError.into()),
                     },
-                    // TODO: For now, it seems like all/most standard close codes should be
-                    // recoverable and thus the shard should reconnect. This should be reevaluated
-                    // later.
-                    _ => return Err(NoneError.into()),
-                },
-                None => return Err(NoneError.into()),
-            },
+                    None => return Err(NoneError.into()),
+                }
+            }
             _ => {}
         }
 
-        let payload = self.deserialize_message(message).await?;
+        let payload = self.deserialize_message(message)?.ok_or(NoneError)?;
 
         trace!("[Shard] Received payload {:?}", payload);
 
@@ -360,9 +798,7 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
 impl<C: GatewayConnector + Send + Sync> Shard<C> {
     #[inline]
     fn next_heartbeat_time(state: &GatewayState<C>) -> Option<Instant> {
-        state
-            .heartbeat_interval
-            .map(|heartbeat_interval| state.last_heartbeat + heartbeat_interval)
+        state.next_heartbeat
     }
 
     #[inline]
@@ -376,8 +812,8 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
 
     async fn heartbeat(&mut self) -> Result<()> {
         let payload = match &self.state {
-            ConnectionState::Connected(state) => Some(Payload::Heartbeat {
-                data: Heartbeat(state.last_seqnum),
+            ConnectionState::Connected(_) => Some(Payload::Heartbeat {
+                data: Heartbeat(self.session.as_ref().map(|session| session.last_seqnum)),
             }),
             _ => None,
         };
@@ -387,6 +823,10 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
 
             if let ConnectionState::Connected(state) = &mut self.state {
                 state.last_heartbeat = Instant::now();
+                state.next_heartbeat = state
+                    .heartbeat_interval
+                    .map(|heartbeat_interval| state.last_heartbeat + heartbeat_interval);
+                state.is_awaiting_heartbeat_ack = true;
             }
 
             self.push(payload).await?;
@@ -395,7 +835,66 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
         Ok(())
     }
 
+    /// Sends a payload without checking the rate limit.
+    async fn send_payload(&mut self, payload: Payload) -> Result<()> {
+        let frame = self.serialize_payload(payload)?;
+
+        trace!("[Shard] Sending frame {:?}", frame);
+
+        let state = self.ensure_connected().await?;
+
+        state.connection.send(frame).await?;
+
+        Ok(())
+    }
+
+    async fn handle_command(&mut self, command: ShardCommand) -> Result<()> {
+        match command {
+            ShardCommand::Send {
+                payload,
+                is_reserved,
+            } => {
+                // The latest presence is also sent when identifying after a reconnect.
+                if let Payload::UpdatePresence { data } = &payload {
+                    self.presence = Some(data.clone());
+                }
+
+                if is_reserved {
+                    self.send_payload(payload).await
+                } else {
+                    self.push(payload).await
+                }
+            }
+        }
+    }
+
+    fn set_status(&mut self, status: ShardStatus) {
+        if let ConnectionState::Connected(state) = &mut self.state {
+            state.status = status;
+        }
+    }
+
+    /// Waits until the shard is allowed to identify, handling close requests
+    /// in the meantime.
+    async fn wait_for_identify(&mut self) -> Result<IdentifyPermit> {
+        let wait = self
+            .identify_queue
+            .wait(self.shard_info.map_or(0, |info| info.id));
+
+        match Self::until_close_requested(&mut self.close_requests, wait).await {
+            Ok(permit) => Ok(permit),
+            Err(request) => self.handle_close_request(request).await,
+        }
+    }
+
     async fn identify(&mut self) -> Result<()> {
+        let permit = match self.identify_permit.take() {
+            Some(permit) => permit,
+            None => self.wait_for_identify().await?,
+        };
+
+        self.set_status(ShardStatus::Identifying);
+
         debug!("[Shard] Identifying");
 
         self.push(Payload::Identify {
@@ -405,6 +904,7 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
                     os: consts::OS.to_string(),
                     browser: env!("CARGO_PKG_NAME").to_string(),
                     device: env!("CARGO_PKG_NAME").to_string(),
+                    version: self.version,
                 },
                 use_payload_compression: Some(
                     if let GatewayCompression::Payload(PayloadCompression::Zlib) = self.compression
@@ -414,6 +914,27 @@ impl<C: GatewayConnector + Send + Sync> Shard<C> {
                         false
                     },
                 ),
+                shard: self.shard_info,
+                intents: self.intents,
+                presence: self.presence.clone(),
+            },
+        })
+        .await?;
+
+        permit.complete();
+        Ok(())
+    }
+
+    async fn resume(&mut self, session: SessionInfo) -> Result<()> {
+        debug!("[Shard] Resuming session {:?}", session.session_id);
+
+        self.set_status(ShardStatus::Resuming);
+
+        self.push(Payload::Resume {
+            data: Resume {
+                token: self.token.clone(),
+                session_id: session.session_id,
+                seqnum: session.last_seqnum,
             },
         })
         .await
@@ -425,8 +946,42 @@ impl<C: GatewayConnector + Send + Sync> PayloadDelegate for Shard<C> {
     async fn dispatch(&mut self, data: &Dispatch) -> Result<()> {
         debug!("[Shard] Received dispatch {{ seqnum: {:?} }}", data.seqnum);
 
-        if let ConnectionState::Connected(state) = &mut self.state {
-            state.last_seqnum = Some(data.seqnum);
+        match &data.event {
+            DispatchEvent::Ready(ready) => {
+                debug!("[Shard] Started session {:?}", ready.session_id());
+
+                if ready.gateway_version() != self.version as u8 {
+                    warn!(
+                        "[Shard] Requested gateway version {:?}, but session uses version {}",
+                        self.version,
+                        ready.gateway_version()
+                    );
+                }
+
+                self.session = Some(SessionInfo {
+                    session_id: ready.session_id().to_string(),
+                    last_seqnum: data.seqnum,
+                    resume_url: ready
+                        .resume_gateway_url()
+                        .unwrap_or_else(|| self.gateway.url())
+                        .to_string(),
+                    shard_info: self.shard_info,
+                });
+                self.set_status(ShardStatus::Connected);
+            }
+            DispatchEvent::Resumed(_) => {
+                debug!("[Shard] Resumed session");
+
+                self.set_status(ShardStatus::Connected);
+            }
+            DispatchEvent::GuildMembersChunk(chunk) => {
+                self.handle.shared().dispatch_member_chunk(chunk);
+            }
+            _ => {}
+        }
+
+        if let Some(session) = &mut self.session {
+            session.last_seqnum = data.seqnum;
         }
 
         Ok(())
@@ -436,6 +991,35 @@ impl<C: GatewayConnector + Send + Sync> PayloadDelegate for Shard<C> {
         self.heartbeat().await
     }
 
+    async fn reconnect_req(&mut self) -> Result<()> {
+        debug!("[Shard] Received reconnect request");
+
+        self.reconnect().await;
+
+        Ok(())
+    }
+
+    async fn invalid_session(&mut self, data: &InvalidSession) -> Result<()> {
+        let InvalidSession(is_resumable) = *data;
+
+        debug!(
+            "[Shard] Session invalidated {{ resumable: {:?} }}",
+            is_resumable
+        );
+
+        if !is_resumable {
+            self.session = None;
+        }
+
+        // The gateway expects clients to wait a random amount of time between 1 and 5
+        // seconds before identifying or resuming again.
+        sleep(Duration::from_millis(fastrand::u64(1000..=5000))).await;
+
+        self.reconnect().await;
+
+        Ok(())
+    }
+
     async fn hello(&mut self, data: &Hello) -> Result<()> {
         debug!("[Shard] Received hello");
 
@@ -445,24 +1029,43 @@ impl<C: GatewayConnector + Send + Sync> PayloadDelegate for Shard<C> {
                 data.heartbeat_interval()
             );
             state.heartbeat_interval = Some(data.heartbeat_interval());
-        }
 
-        // TODO: Handle resuming
+            // The first heartbeat is sent after a random fraction of the interval, so that
+            // shards which connected at the same time don't all heartbeat at once.
+            state.next_heartbeat =
+                Some(Instant::now() + data.heartbeat_interval().mul_f64(fastrand::f64()));
+
+            self.handle
+                .shared()
+                .ratelimiter()
+                .set_heartbeat_interval(data.heartbeat_interval());
+        }
 
-        self.identify().await?;
-        self.heartbeat().await?;
+        match self.session.clone() {
+            Some(session) => self.resume(session).await?,
+            None => self.identify().await?,
+        }
 
         Ok(())
     }
 
     async fn heartbeat_ack(&mut self) -> Result<()> {
         if let ConnectionState::Connected(state) = &mut self.state {
+            let latency = state.last_heartbeat.elapsed();
+
             debug!(
                 "[Shard] Heartbeat acknowledged with latency of {:?}",
-                state.last_heartbeat.elapsed()
+                latency
             );
 
+            // Acknowledgements of heartbeats requested by the gateway may arrive while no
+            // heartbeat is being awaited.
+            if state.is_awaiting_heartbeat_ack {
+                self.handle.shared().latency().record(latency);
+            }
+
             state.last_heartbeat_ack = Instant::now();
+            state.is_awaiting_heartbeat_ack = false;
         }
 
         Ok(())
@@ -476,8 +1079,10 @@ impl<C: GatewayConnector + Send + Sync> AsyncStream for Shard<C> {
 
     async fn next(&mut self) -> Option<Result<Self::Item, Self::Error>> {
         loop {
-            // TODO: This needs proper reconnect/shutdown logic. Retrying indefinitely will
-            // not solve all problems.
+            if let ConnectionState::Closed = self.state {
+                return None;
+            }
+
             match self.fetch_next().await {
                 Err(err) => match err.downcast_ref::<NoneError>() {
                     Some(NoneError) => continue,
@@ -494,19 +1099,562 @@ impl<C: GatewayConnector + Send + Sync> AsyncSink for Shard<C> {
     type Item = Payload;
     type Error = anyhow::Error;
 
+    /// Sends a payload once the rate limit allows it. Heartbeats are never
+    /// delayed.
     async fn push(&mut self, payload: Self::Item) -> Result<(), Self::Error> {
-        let message = self.serialize_payload(payload)?;
-
-        trace!("[Shard] Sending message {:?}", message);
+        if let Payload::Heartbeat { .. } = payload {
+            self.handle.shared().ratelimiter().acquire_heartbeat();
+        } else {
+            loop {
+                let result = self.handle.shared().ratelimiter().try_acquire();
+
+                match result {
+                    Ok(()) => break,
+                    Err(delay) => {
+                        debug!("[Shard] Rate limited, delaying payload by {:?}", delay);
+                        sleep(delay).await;
+                    }
+                }
+            }
+        }
 
-        let state = self.ensure_connected().await?;
+        self.send_payload(payload).await
+    }
 
-        state.connection.send(message).await?;
+    /// Closes the connection and ends the session. Use
+    /// [`Shard::shutdown`] to keep the session resumable instead.
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        self.shutdown(false).await;
 
         Ok(())
     }
+}
 
-    async fn close(&mut self) -> Result<(), Self::Error> {
-        todo!()
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compress, Compression, FlushCompress};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::gateway::{
+        test_support::{self, FakeGateway, Received, ScriptedConnection, Step},
+        MemoryGatewayConnector,
+    };
+
+    fn memory_shard(
+        gateway: &FakeGateway,
+        connector: MemoryGatewayConnector,
+        encoding: PayloadEncoding,
+        compression: GatewayCompression,
+    ) -> Shard<MemoryGatewayConnector> {
+        Shard::new(
+            gateway.gateway(),
+            "token".to_owned(),
+            encoding,
+            compression,
+            connector,
+        )
+    }
+
+    /// The READY and GUILD_CREATE dispatches with which sessions usually
+    /// start, encoded as JSON.
+    fn ready_and_guild_create() -> Vec<Vec<u8>> {
+        let ready = json!({
+            "op": 0,
+            "s": 1,
+            "t": "READY",
+            "d": {
+                "v": 8,
+                "session_id": "session",
+                "guilds": [{ "id": "1", "unavailable": true }],
+            },
+        });
+        let guild_create = json!({
+            "op": 0,
+            "s": 2,
+            "t": "GUILD_CREATE",
+            "d": test_support::guild_create(1, "Guild", Vec::new()),
+        });
+
+        [ready, guild_create]
+            .iter()
+            .map(|payload| serde_json::to_vec(payload).unwrap())
+            .collect()
+    }
+
+    fn script_with_frames(frames: Vec<Vec<u8>>) -> ScriptedConnection {
+        let mut script = ScriptedConnection::new();
+        script.hello(Duration::from_secs(40)).expect_identify();
+
+        for frame in frames {
+            script.step(Step::Frame(Frame::Binary(frame)));
+        }
+
+        script
+    }
+
+    /// Polls the shard until the next dispatched event, returning its name.
+    async fn next_event<C: GatewayConnector + Send + Sync>(shard: &mut Shard<C>) -> &'static str {
+        loop {
+            if let Payload::Dispatch(dispatch) = shard.next().await.unwrap().unwrap() {
+                return dispatch.event.kind().name().unwrap();
+            }
+        }
+    }
+
+    /// The data of the payload with the given opcode which the server received
+    /// on the given connection.
+    fn received_payload(gateway: &FakeGateway, connection: usize, op: u8) -> Option<Value> {
+        gateway
+            .received()
+            .into_iter()
+            .find_map(|received| match received {
+                Received::Payload {
+                    connection: index,
+                    op: received_op,
+                    data,
+                } if index == connection && received_op == op => Some(data),
+                _ => None,
+            })
+    }
+
+    #[tokio::test]
+    async fn identifies_and_closes() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway.script(
+            ScriptedConnection::new()
+                .hello(Duration::from_secs(40))
+                .expect_identify()
+                .ready("session", &gateway.url()),
+        );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(shard.status(), ShardStatus::Connected);
+
+        let session = shard.shutdown(true).await.unwrap();
+        assert_eq!(session.session_id, "session");
+        assert_eq!(session.last_seqnum, 1);
+        assert!(shard.next().await.is_none());
+
+        // Give the server a chance to receive the close frame.
+        sleep(Duration::from_millis(10)).await;
+
+        let received = gateway.received();
+        let identify = received.iter().find_map(|received| match received {
+            Received::Payload { op: 2, data, .. } => Some(data),
+            _ => None,
+        });
+
+        assert_eq!(identify.unwrap()["token"], "token");
+        assert_eq!(
+            received.last(),
+            Some(&Received::Close {
+                connection: 0,
+                code: Some(4000),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn inflates_payload_compressed_messages() {
+        let frames = ready_and_guild_create()
+            .into_iter()
+            .map(|payload| {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&payload).unwrap();
+                encoder.finish().unwrap()
+            })
+            .collect();
+
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway.script(&script_with_frames(frames));
+
+        let compression = GatewayCompression::Payload(PayloadCompression::Zlib);
+        let mut shard = memory_shard(&gateway, connector, PayloadEncoding::Json, compression);
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(next_event(&mut shard).await, "GUILD_CREATE");
+    }
+
+    #[tokio::test]
+    async fn inflates_transport_compressed_messages() {
+        // Every message shares the same zlib context, and is flushed with the
+        // 00 00 ff ff suffix.
+        let mut compress = Compress::new(Compression::default(), true);
+        let mut messages = ready_and_guild_create().into_iter().map(|payload| {
+            let mut output = Vec::with_capacity(payload.len() + 64);
+            compress
+                .compress_vec(&payload, &mut output, FlushCompress::Sync)
+                .unwrap();
+            output
+        });
+
+        let ready = messages.next().unwrap();
+        let mut guild_create = messages.next().unwrap();
+
+        // The gateway may split a message across several frames.
+        let rest = guild_create.split_off(guild_create.len() / 2);
+        let frames = vec![ready, guild_create, rest];
+
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway.script(&script_with_frames(frames));
+
+        let compression = GatewayCompression::Transport(TransportCompression::ZlibStream);
+        let mut shard = memory_shard(&gateway, connector, PayloadEncoding::Json, compression);
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(next_event(&mut shard).await, "GUILD_CREATE");
+    }
+
+    #[tokio::test]
+    async fn decodes_uncompressed_etf_messages() {
+        // Payload compression is optional per message, so uncompressed frames
+        // should be decoded as they are.
+        let compressions = vec![
+            GatewayCompression::None,
+            GatewayCompression::Payload(PayloadCompression::Zlib),
+        ];
+
+        for compression in compressions {
+            let (gateway, connector) = FakeGateway::in_memory();
+            gateway.script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_identify()
+                    .ready("session", &gateway.url())
+                    .dispatch(
+                        "GUILD_CREATE",
+                        test_support::guild_create(1, "Guild", Vec::new()),
+                    ),
+            );
+
+            let mut shard = memory_shard(&gateway, connector, PayloadEncoding::Etf, compression);
+
+            assert_eq!(next_event(&mut shard).await, "READY");
+            assert_eq!(next_event(&mut shard).await, "GUILD_CREATE");
+
+            let identify = gateway
+                .received()
+                .into_iter()
+                .find_map(|received| match received {
+                    Received::Payload { op: 2, data, .. } => Some(data),
+                    _ => None,
+                });
+            assert_eq!(identify.unwrap()["token"], "token");
+        }
+    }
+
+    #[tokio::test]
+    async fn closes_after_unrecoverable_close_code() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway.script(
+            ScriptedConnection::new()
+                .hello(Duration::from_secs(40))
+                .expect_identify()
+                .close(4004, "Authentication failed"),
+        );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        let err = loop {
+            match shard.next().await.unwrap() {
+                Ok(_) => {}
+                Err(err) => break err,
+            }
+        };
+
+        match err.downcast_ref::<GatewayError>() {
+            Some(GatewayError::Closed {
+                code,
+                is_recoverable,
+                ..
+            }) => {
+                assert_eq!(*code, GatewayCloseCode::AuthenticationFailed);
+                assert!(!is_recoverable);
+            }
+            _ => panic!(
+                "Expected the gateway to close the connection, got {:?}",
+                err
+            ),
+        }
+
+        assert!(shard.next().await.is_none());
+        assert_eq!(gateway.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn resumes_after_connection_drop() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_identify()
+                    .ready("session", &gateway.url())
+                    .drop_connection(),
+            )
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_resume()
+                    .resumed(),
+            );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(next_event(&mut shard).await, "RESUMED");
+
+        let resume = gateway
+            .received()
+            .into_iter()
+            .find(|received| received.connection() == 1 && received.op() == Some(6));
+
+        match resume {
+            Some(Received::Payload { data, .. }) => {
+                assert_eq!(data["session_id"], "session");
+                assert_eq!(data["seq"], 1);
+            }
+            _ => panic!("Expected the shard to resume"),
+        }
+        assert_eq!(gateway.connections(), 2);
+    }
+
+    #[tokio::test]
+    async fn resumes_when_asked_to_reconnect() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_identify()
+                    .ready("session", &gateway.url())
+                    .reconnect(),
+            )
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_resume()
+                    .resumed(),
+            );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(next_event(&mut shard).await, "RESUMED");
+
+        // The first connection is closed with a code which keeps the session resumable.
+        assert!(gateway.received().contains(&Received::Close {
+            connection: 0,
+            code: Some(4000),
+        }));
+        assert_eq!(
+            received_payload(&gateway, 1, 6).unwrap()["session_id"],
+            "session"
+        );
+    }
+
+    #[tokio::test]
+    async fn resumes_resumable_invalid_sessions() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_identify()
+                    .ready("session", &gateway.url())
+                    .invalid_session(true),
+            )
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_resume()
+                    .resumed(),
+            );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(next_event(&mut shard).await, "RESUMED");
+
+        assert_eq!(
+            received_payload(&gateway, 1, 6).unwrap()["session_id"],
+            "session"
+        );
+        assert!(received_payload(&gateway, 1, 2).is_none());
+    }
+
+    #[tokio::test]
+    async fn identifies_again_after_unresumable_invalid_sessions() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_identify()
+                    .ready("session", &gateway.url())
+                    .invalid_session(false),
+            )
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_identify()
+                    .ready("new session", &gateway.url()),
+            );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(shard.session().unwrap().session_id, "session");
+
+        assert_eq!(next_event(&mut shard).await, "READY");
+        assert_eq!(shard.session().unwrap().session_id, "new session");
+
+        assert!(received_payload(&gateway, 1, 2).is_some());
+        assert!(received_payload(&gateway, 1, 6).is_none());
+    }
+
+    #[tokio::test]
+    async fn reconnects_zombie_connections() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        gateway
+            .script(
+                ScriptedConnection::new()
+                    .with_heartbeat_acks(false)
+                    .hello(Duration::from_millis(100))
+                    .expect_identify()
+                    .ready("session", &gateway.url()),
+            )
+            .script(
+                ScriptedConnection::new()
+                    .hello(Duration::from_secs(40))
+                    .expect_resume()
+                    .resumed(),
+            );
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        // The status after each payload, without repeats.
+        let mut statuses = Vec::new();
+
+        loop {
+            let payload = shard.next().await.unwrap().unwrap();
+
+            if statuses.last() != Some(&shard.status()) {
+                statuses.push(shard.status());
+            }
+
+            if let Payload::Dispatch(dispatch) = payload {
+                if dispatch.event.kind().name() == Some("RESUMED") {
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(
+            statuses,
+            vec![
+                ShardStatus::Identifying,
+                ShardStatus::Connected,
+                ShardStatus::Resuming,
+                ShardStatus::Connected,
+            ]
+        );
+
+        // The unacknowledged heartbeat was sent on the first connection, which was then
+        // closed while keeping the session resumable.
+        assert!(received_payload(&gateway, 0, 1).is_some());
+        assert!(gateway.received().contains(&Received::Close {
+            connection: 0,
+            code: Some(4000),
+        }));
+        assert_eq!(
+            received_payload(&gateway, 1, 6).unwrap()["session_id"],
+            "session"
+        );
+    }
+
+    #[tokio::test]
+    async fn shuts_down_while_waiting_to_identify() {
+        let (gateway, connector) = FakeGateway::in_memory();
+
+        // Another shard holds the only identify slot.
+        let queue = Arc::new(LocalIdentifyQueue::default());
+        let _permit = queue.wait(1).await;
+
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+        shard.with_identify_queue(queue);
+        let handle = shard.handle();
+
+        let (next, session) = tokio::join!(shard.next(), handle.shutdown(true));
+
+        assert!(next.is_none());
+        assert!(session.unwrap().is_none());
+        assert_eq!(gateway.connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn answers_pending_close_requests_once_closed() {
+        let (gateway, connector) = FakeGateway::in_memory();
+        let mut shard = memory_shard(
+            &gateway,
+            connector,
+            PayloadEncoding::Json,
+            GatewayCompression::None,
+        );
+
+        // The request is sent, but the shard is closed before it is polled.
+        let handle = shard.handle();
+        let request = tokio::spawn(async move { handle.shutdown(true).await });
+        tokio::task::yield_now().await;
+
+        shard.shutdown(true).await;
+
+        assert!(request.await.unwrap().unwrap().is_none());
+        assert!(shard.handle().shutdown(true).await.unwrap().is_none());
     }
 }
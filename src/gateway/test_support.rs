@@ -0,0 +1,607 @@
+//! A gateway server which follows a script, for testing how shards
+//! handle reconnects, resumes, invalid sessions and zombie connections.
+//!
+//! The server speaks the encoding requested by the shard, without
+//! compression. Compressed payloads can be tested by sending raw frames with
+//! [`Step::Frame`].
+
+use std::{
+    collections::VecDeque,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_tungstenite::{
+    tokio::accept_hdr_async,
+    tungstenite::handshake::server::{ErrorResponse, Request, Response},
+};
+use log::debug;
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+    time::sleep,
+};
+
+use super::{
+    etf, CloseFrame, Frame, GatewayConnectionParams, GatewayTransport, GatewayVersion,
+    MemoryGatewayConnector, MemoryGatewayListener, PayloadEncoding, WsTransport,
+};
+use crate::models::Gateway;
+
+/// A step of a [`ScriptedConnection`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Step {
+    /// Sends a raw payload.
+    Send(Value),
+
+    /// Sends a raw frame, e.g. a compressed payload.
+    Frame(Frame),
+
+    /// Sends a Dispatch payload with the next sequence number.
+    Dispatch { event: String, data: Value },
+
+    /// Dispatches Ready for the given session, with the gateway version
+    /// requested by the shard.
+    Ready {
+        session_id: String,
+        resume_url: String,
+    },
+
+    /// Waits until a payload with the given opcode is received. Heartbeats
+    /// received in the meantime are still acknowledged.
+    Expect(u8),
+
+    /// Waits for the given duration. Heartbeats received in the meantime are
+    /// still acknowledged.
+    Sleep(Duration),
+
+    /// Sends a close frame and ends the connection.
+    Close { code: u16, reason: String },
+
+    /// Ends the connection without sending a close frame.
+    Drop,
+}
+
+/// The steps that the server follows on one connection.
+///
+/// Once all steps have been followed, the connection is kept open until the
+/// shard closes it.
+#[derive(Clone, Debug)]
+pub struct ScriptedConnection {
+    steps: Vec<Step>,
+    acks_heartbeats: bool,
+}
+
+impl ScriptedConnection {
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            acks_heartbeats: true,
+        }
+    }
+
+    /// Sets whether heartbeats are acknowledged. If not, the connection
+    /// behaves like a zombie connection.
+    pub fn with_heartbeat_acks(&mut self, acks_heartbeats: bool) -> &mut Self {
+        self.acks_heartbeats = acks_heartbeats;
+        self
+    }
+
+    pub fn step(&mut self, step: Step) -> &mut Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn hello(&mut self, heartbeat_interval: Duration) -> &mut Self {
+        self.step(Step::Send(json!({
+            "op": 10,
+            "d": { "heartbeat_interval": heartbeat_interval.as_millis() as u64 },
+        })))
+    }
+
+    pub fn expect_identify(&mut self) -> &mut Self {
+        self.step(Step::Expect(2))
+    }
+
+    pub fn expect_resume(&mut self) -> &mut Self {
+        self.step(Step::Expect(6))
+    }
+
+    pub fn dispatch(&mut self, event: impl Into<String>, data: Value) -> &mut Self {
+        self.step(Step::Dispatch {
+            event: event.into(),
+            data,
+        })
+    }
+
+    /// Dispatches Ready for the given session, which is resumed through
+    /// `resume_url`.
+    pub fn ready(&mut self, session_id: &str, resume_url: &str) -> &mut Self {
+        self.step(Step::Ready {
+            session_id: session_id.to_owned(),
+            resume_url: resume_url.to_owned(),
+        })
+    }
+
+    pub fn resumed(&mut self) -> &mut Self {
+        self.dispatch("RESUMED", json!({}))
+    }
+
+    pub fn reconnect(&mut self) -> &mut Self {
+        self.step(Step::Send(json!({ "op": 7, "d": null })))
+    }
+
+    pub fn invalid_session(&mut self, is_resumable: bool) -> &mut Self {
+        self.step(Step::Send(json!({ "op": 9, "d": is_resumable })))
+    }
+
+    pub fn sleep(&mut self, duration: Duration) -> &mut Self {
+        self.step(Step::Sleep(duration))
+    }
+
+    pub fn close(&mut self, code: u16, reason: impl Into<String>) -> &mut Self {
+        self.step(Step::Close {
+            code,
+            reason: reason.into(),
+        })
+    }
+
+    pub fn drop_connection(&mut self) -> &mut Self {
+        self.step(Step::Drop)
+    }
+}
+
+impl Default for ScriptedConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The data of a GUILD_CREATE dispatch for a guild with only the required
+/// fields, and the given channels.
+pub fn guild_create(guild_id: u64, name: &str, channels: Vec<Value>) -> Value {
+    json!({
+        "id": guild_id.to_string(),
+        "name": name,
+        "region": "europe",
+        "preferred_locale": "en-US",
+        "verification_level": 0,
+        "default_message_notifications": 0,
+        "explicit_content_filter": 0,
+        "mfa_level": 0,
+        "features": [],
+        "roles": [],
+        "afk_timeout": 300,
+        "premium_tier": 0,
+        "premium_subscription_count": 0,
+        "system_channel_flags": 0,
+        "channels": channels,
+    })
+}
+
+/// Something the server received from a shard.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Received {
+    Payload {
+        /// The index of the connection on which it was received.
+        connection: usize,
+        op: u8,
+        data: Value,
+    },
+    Close {
+        connection: usize,
+        code: Option<u16>,
+    },
+}
+
+impl Received {
+    pub fn connection(&self) -> usize {
+        match self {
+            Received::Payload { connection, .. } | Received::Close { connection, .. } => {
+                *connection
+            }
+        }
+    }
+
+    /// The opcode of the payload, if a payload was received.
+    pub fn op(&self) -> Option<u8> {
+        match self {
+            Received::Payload { op, .. } => Some(*op),
+            Received::Close { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeGatewayState {
+    scripts: VecDeque<ScriptedConnection>,
+    received: Vec<Received>,
+    connections: usize,
+    seqnum: u64,
+}
+
+impl FakeGatewayState {
+    /// Registers a new connection, returning its index and script.
+    fn accept(&mut self) -> (usize, Option<ScriptedConnection>) {
+        self.connections += 1;
+
+        (self.connections - 1, self.scripts.pop_front())
+    }
+}
+
+/// A gateway server, either listening on a local port or accepting the
+/// connections of a [`MemoryGatewayConnector`].
+///
+/// Each connection follows the next script passed to
+/// [`script`](Self::script). Connections for which there is no script are
+/// closed right away. The server is stopped when dropped.
+#[derive(Debug)]
+pub struct FakeGateway {
+    url: String,
+    state: Arc<Mutex<FakeGatewayState>>,
+    task: JoinHandle<()>,
+}
+
+impl FakeGateway {
+    /// Starts a WebSocket server on a local port.
+    pub async fn bind() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr: SocketAddr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(FakeGatewayState::default()));
+
+        let task = tokio::spawn(Self::listen(listener, state.clone()));
+
+        Ok(Self {
+            url: format!("ws://{}", addr),
+            state,
+            task,
+        })
+    }
+
+    /// Starts a server which accepts the connections of the returned
+    /// connector, without using the network. Must be called from within a
+    /// Tokio runtime.
+    pub fn in_memory() -> (Self, MemoryGatewayConnector) {
+        let (connector, listener) = MemoryGatewayConnector::new();
+        let state = Arc::new(Mutex::new(FakeGatewayState::default()));
+
+        let task = tokio::spawn(Self::listen_in_memory(listener, state.clone()));
+
+        let gateway = Self {
+            url: "memory://gateway".to_owned(),
+            state,
+            task,
+        };
+
+        (gateway, connector)
+    }
+
+    /// The gateway to point shards at.
+    pub fn gateway(&self) -> Gateway {
+        Gateway::new(self.url())
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Adds a script for the next connection.
+    pub fn script(&self, script: &ScriptedConnection) -> &Self {
+        self.state.lock().unwrap().scripts.push_back(script.clone());
+        self
+    }
+
+    /// Everything received so far, in order.
+    pub fn received(&self) -> Vec<Received> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// The number of connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+
+    async fn listen(listener: TcpListener, state: Arc<Mutex<FakeGatewayState>>) {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (index, script) = state.lock().unwrap().accept();
+            let state = state.clone();
+
+            tokio::spawn(async move {
+                let result = match Self::accept_ws(stream).await {
+                    Ok((transport, params)) => {
+                        let connection = FakeConnection::new(index, transport, params, state);
+
+                        connection.run(script).await
+                    }
+                    Err(err) => Err(err),
+                };
+
+                if let Err(err) = result {
+                    debug!("[FakeGateway] Connection {:?} failed {:?}", index, err);
+                }
+            });
+        }
+    }
+
+    /// Completes the WebSocket handshake, returning the connection parameters
+    /// requested in the query.
+    async fn accept_ws(
+        stream: TcpStream,
+    ) -> Result<(impl GatewayTransport, Option<GatewayConnectionParams>)> {
+        let mut params = None;
+
+        let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+            params = request.uri().query().and_then(|query| {
+                serde_urlencoded::from_str::<GatewayConnectionParams>(query).ok()
+            });
+
+            Ok(response)
+        };
+
+        let ws_stream = accept_hdr_async(stream, callback).await?;
+
+        Ok((WsTransport::new(ws_stream), params))
+    }
+
+    async fn listen_in_memory(
+        mut listener: MemoryGatewayListener,
+        state: Arc<Mutex<FakeGatewayState>>,
+    ) {
+        while let Some((transport, conn_params)) = listener.accept().await {
+            let (index, script) = state.lock().unwrap().accept();
+
+            let connection =
+                FakeConnection::new(index, transport, Some(conn_params), state.clone());
+
+            tokio::spawn(async move {
+                if let Err(err) = connection.run(script).await {
+                    debug!("[FakeGateway] Connection {:?} failed {:?}", index, err);
+                }
+            });
+        }
+    }
+}
+
+impl Drop for FakeGateway {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct FakeConnection<T> {
+    index: usize,
+    transport: T,
+    version: GatewayVersion,
+    encoding: PayloadEncoding,
+    state: Arc<Mutex<FakeGatewayState>>,
+    acks_heartbeats: bool,
+}
+
+impl<T: GatewayTransport> FakeConnection<T> {
+    fn new(
+        index: usize,
+        transport: T,
+        params: Option<GatewayConnectionParams>,
+        state: Arc<Mutex<FakeGatewayState>>,
+    ) -> Self {
+        let (version, encoding) = match params {
+            Some(params) => (params.version(), params.encoding().clone()),
+            None => Default::default(),
+        };
+
+        Self {
+            index,
+            transport,
+            version,
+            encoding,
+            state,
+            acks_heartbeats: true,
+        }
+    }
+
+    async fn run(mut self, script: Option<ScriptedConnection>) -> Result<()> {
+        let script = match script {
+            Some(script) => script,
+            None => return self.close(1000, "No script for this connection").await,
+        };
+
+        self.acks_heartbeats = script.acks_heartbeats;
+
+        for step in script.steps {
+            match step {
+                Step::Send(payload) => self.send(payload).await?,
+                Step::Frame(frame) => self.transport.send(frame).await?,
+                Step::Dispatch { event, data } => self.dispatch(&event, data).await?,
+                Step::Ready {
+                    session_id,
+                    resume_url,
+                } => {
+                    let data = json!({
+                        "v": self.version as u8,
+                        "session_id": session_id,
+                        "guilds": [],
+                        "resume_gateway_url": resume_url,
+                    });
+
+                    self.dispatch("READY", data).await?;
+                }
+                Step::Expect(expected_op) => loop {
+                    match self.receive().await? {
+                        Some(op) if op == expected_op => break,
+                        Some(_) => {}
+                        None => {
+                            return Err(anyhow!(
+                                "Connection closed while expecting {:?}",
+                                expected_op
+                            ))
+                        }
+                    }
+                },
+                Step::Sleep(duration) => {
+                    let deadline = sleep(duration);
+                    tokio::pin!(deadline);
+
+                    loop {
+                        tokio::select! {
+                            _ = &mut deadline => break,
+                            op = self.receive() => {
+                                if op?.is_none() {
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    }
+                }
+                Step::Close { code, reason } => return self.close(code, &reason).await,
+                Step::Drop => return Ok(()),
+            }
+        }
+
+        while self.receive().await?.is_some() {}
+
+        Ok(())
+    }
+
+    async fn dispatch(&mut self, event: &str, data: Value) -> Result<()> {
+        let seqnum = {
+            let mut state = self.state.lock().unwrap();
+            state.seqnum += 1;
+            state.seqnum
+        };
+
+        self.send(json!({ "op": 0, "s": seqnum, "t": event, "d": data }))
+            .await
+    }
+
+    async fn send(&mut self, payload: Value) -> Result<()> {
+        let frame = match self.encoding {
+            PayloadEncoding::Json => Frame::Text(serde_json::to_string(&payload)?),
+            PayloadEncoding::Etf => Frame::Binary(etf::to_vec(&payload)?),
+        };
+
+        self.transport.send(frame).await
+    }
+
+    async fn close(&mut self, code: u16, reason: &str) -> Result<()> {
+        self.transport
+            .send(Frame::Close(Some(CloseFrame {
+                code,
+                reason: reason.to_owned(),
+            })))
+            .await
+    }
+
+    fn record(&self, received: Received) {
+        self.state.lock().unwrap().received.push(received);
+    }
+
+    /// Receives and records the next payload, acknowledging it if it is a
+    /// heartbeat and heartbeats are acknowledged. Returns its opcode, or `None`
+    /// if the connection was closed.
+    async fn receive(&mut self) -> Result<Option<u8>> {
+        let frame = match self.transport.receive().await {
+            Some(frame) => frame?,
+            None => return Ok(None),
+        };
+
+        let mut payload: Value = match frame {
+            Frame::Text(text) => serde_json::from_str(&text)?,
+            Frame::Binary(bytes) => etf::from_slice(&bytes)?,
+            Frame::Close(frame) => {
+                self.record(Received::Close {
+                    connection: self.index,
+                    code: frame.map(|frame| frame.code),
+                });
+
+                return Ok(None);
+            }
+        };
+
+        let op = payload["op"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Received a payload without an opcode"))? as u8;
+
+        self.record(Received::Payload {
+            connection: self.index,
+            op,
+            data: payload["d"].take(),
+        });
+
+        if op == 1 && self.acks_heartbeats {
+            self.send(json!({ "op": 11 })).await?;
+        }
+
+        Ok(Some(op))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{dispatch::DispatchEvent, Payload},
+        gateway::Shard,
+        util::AsyncStream,
+    };
+
+    async fn next_ready<S>(shard: &mut S) -> u8
+    where
+        S: AsyncStream<Item = Payload, Error = anyhow::Error>,
+    {
+        loop {
+            if let Payload::Dispatch(dispatch) = shard.next().await.unwrap().unwrap() {
+                if let DispatchEvent::Ready(ready) = dispatch.event {
+                    return ready.gateway_version();
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn ready_echoes_requested_version() {
+        let gateway = FakeGateway::bind().await.unwrap();
+        gateway.script(
+            ScriptedConnection::new()
+                .hello(Duration::from_secs(40))
+                .expect_identify()
+                .ready("session", &gateway.url()),
+        );
+
+        let mut shard = Shard::default_with(gateway.gateway(), "token".to_owned());
+        shard.with_version(GatewayVersion::V10);
+
+        assert_eq!(next_ready(&mut shard).await, 10);
+        assert_eq!(gateway.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn sleep_acks_heartbeats() {
+        let gateway = FakeGateway::bind().await.unwrap();
+        gateway.script(
+            ScriptedConnection::new()
+                .hello(Duration::from_millis(50))
+                .expect_identify()
+                .sleep(Duration::from_millis(300))
+                .ready("session", &gateway.url()),
+        );
+
+        let mut shard = Shard::default_with(gateway.gateway(), "token".to_owned());
+        next_ready(&mut shard).await;
+
+        let heartbeats = gateway
+            .received()
+            .iter()
+            .filter(|received| received.op() == Some(1))
+            .count();
+
+        // An unacknowledged heartbeat would have made the shard reconnect.
+        assert!(heartbeats >= 2);
+        assert_eq!(gateway.connections(), 1);
+    }
+}
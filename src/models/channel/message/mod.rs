@@ -1,5 +1,4 @@
-use serde::{Serialize, Deserialize};
-use serde_repr::{Deserialize_repr, Serialize_repr};
+use serde::{Deserialize, Serialize};
 
 use crate::models::prelude::*;
 
@@ -8,8 +7,14 @@ create_id!(pub MessageId {
 });
 
 impl MessageId {
+    pub(crate) fn new(id: Snowflake, channel_id: Snowflake) -> Self {
+        Self { id, channel_id }
+    }
+
     pub fn channel_id(&self) -> TextChannelId {
-        TextChannelId { id: self.channel_id }
+        TextChannelId {
+            id: self.channel_id,
+        }
     }
 }
 
@@ -22,9 +27,12 @@ pub struct Message {
     #[serde(rename = "_received_at", default = "Utc::now")]
     pub(crate) received_at: DateTime<Utc>,
 
-    // #[serde(rename = "type")]
-    // pub kind: MessageKind,
+    #[serde(rename = "type")]
+    pub kind: MessageKind,
 
+    /// Empty if the message was received through gateway v10 or later without
+    /// the `MESSAGE_CONTENT` intent.
+    #[serde(default)]
     pub content: String,
 
     #[serde(rename = "timestamp")]
@@ -42,25 +50,105 @@ impl Message {
     }
 }
 
-#[derive(Clone, Debug, Deserialize_repr, Serialize_repr)]
-#[repr(u8)]
+/// The type of a message, sent as an integer.
+///
+/// Types which were added after this was written are decoded as `Unknown`, so
+/// that messages of new types can still be received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "u8", into = "u8")]
 #[non_exhaustive]
 pub enum MessageKind {
-    Default = 0,
-    RecipientAdd = 1,
-    RecipientRemove = 2,
-    Call = 3,
-    ChannelNameChange = 4,
-    ChannelIconChange = 5,
-    ChannelPinnedMessage = 6,
-    GuildMemberJoin = 7,
-    UserPremiumGuildSubscription = 8,
-    UserPremiumGuildSubscriptionTier1 = 9,
-    UserPremiumGuildSubscriptionTier2 = 10,
-    UserPremiumGuildSubscriptionTier3 = 11,
-    ChannelFollowAdd = 12,
-    GuildDiscoveryDisqualified = 14,
-    GuildDiscoveryRequalified = 15,
-    Reply = 19,
-    ApplicationCommand = 20,
+    Default,
+    RecipientAdd,
+    RecipientRemove,
+    Call,
+    ChannelNameChange,
+    ChannelIconChange,
+    ChannelPinnedMessage,
+    GuildMemberJoin,
+    UserPremiumGuildSubscription,
+    UserPremiumGuildSubscriptionTier1,
+    UserPremiumGuildSubscriptionTier2,
+    UserPremiumGuildSubscriptionTier3,
+    ChannelFollowAdd,
+    GuildDiscoveryDisqualified,
+    GuildDiscoveryRequalified,
+    Reply,
+    ApplicationCommand,
+    Unknown(u8),
+}
+
+impl From<u8> for MessageKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => MessageKind::Default,
+            1 => MessageKind::RecipientAdd,
+            2 => MessageKind::RecipientRemove,
+            3 => MessageKind::Call,
+            4 => MessageKind::ChannelNameChange,
+            5 => MessageKind::ChannelIconChange,
+            6 => MessageKind::ChannelPinnedMessage,
+            7 => MessageKind::GuildMemberJoin,
+            8 => MessageKind::UserPremiumGuildSubscription,
+            9 => MessageKind::UserPremiumGuildSubscriptionTier1,
+            10 => MessageKind::UserPremiumGuildSubscriptionTier2,
+            11 => MessageKind::UserPremiumGuildSubscriptionTier3,
+            12 => MessageKind::ChannelFollowAdd,
+            14 => MessageKind::GuildDiscoveryDisqualified,
+            15 => MessageKind::GuildDiscoveryRequalified,
+            19 => MessageKind::Reply,
+            20 => MessageKind::ApplicationCommand,
+            kind => MessageKind::Unknown(kind),
+        }
+    }
+}
+
+impl From<MessageKind> for u8 {
+    fn from(kind: MessageKind) -> Self {
+        match kind {
+            MessageKind::Default => 0,
+            MessageKind::RecipientAdd => 1,
+            MessageKind::RecipientRemove => 2,
+            MessageKind::Call => 3,
+            MessageKind::ChannelNameChange => 4,
+            MessageKind::ChannelIconChange => 5,
+            MessageKind::ChannelPinnedMessage => 6,
+            MessageKind::GuildMemberJoin => 7,
+            MessageKind::UserPremiumGuildSubscription => 8,
+            MessageKind::UserPremiumGuildSubscriptionTier1 => 9,
+            MessageKind::UserPremiumGuildSubscriptionTier2 => 10,
+            MessageKind::UserPremiumGuildSubscriptionTier3 => 11,
+            MessageKind::ChannelFollowAdd => 12,
+            MessageKind::GuildDiscoveryDisqualified => 14,
+            MessageKind::GuildDiscoveryRequalified => 15,
+            MessageKind::Reply => 19,
+            MessageKind::ApplicationCommand => 20,
+            MessageKind::Unknown(kind) => kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn decodes_unknown_message_kinds() {
+        for &(kind, expected) in &[(19, MessageKind::Reply), (23, MessageKind::Unknown(23))] {
+            let message: Message = serde_json::from_value(json!({
+                "id": "81384788765712384",
+                "channel_id": "81384788765712384",
+                "type": kind,
+                "content": "Hello",
+                "timestamp": "2021-01-01T00:00:00+00:00",
+                "edited_timestamp": null,
+            }))
+            .unwrap();
+
+            assert_eq!(message.kind, expected);
+            assert_eq!(serde_json::to_value(&message).unwrap()["type"], kind);
+        }
+    }
 }
@@ -1,53 +1,263 @@
 pub mod message;
 pub mod text;
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, Unexpected},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use serde_repr::{Deserialize_repr as DeserializeRepr, Serialize_repr as SerializeRepr};
 
 use crate::models::prelude::*;
 
 create_id!(pub ChannelId {});
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
+#[derive(Debug, Clone)]
 pub enum Channel {
-    #[serde(rename = "0")]
     GuildText(GuildTextChannel),
+    Direct(PartialChannel),
+    GuildVoice(GuildVoiceChannel),
+    Group(PartialChannel),
+    GuildCategory(GuildCategoryChannel),
+    GuildNews(PartialChannel),
+    GuildStore(PartialChannel),
 
-    #[serde(rename = "1")]
-    Direct,
+    /// A channel of a type which isn't supported yet, such as stage or forum
+    /// channels.
+    Unknown {
+        kind: u8,
+        channel: PartialChannel,
+    },
+}
 
-    #[serde(rename = "2")]
-    GuildVoice(GuildVoiceChannel),
+impl Channel {
+    /// Returns the numeric channel type used by Discord.
+    pub fn kind(&self) -> u8 {
+        match self {
+            Channel::GuildText(_) => 0,
+            Channel::Direct(_) => 1,
+            Channel::GuildVoice(_) => 2,
+            Channel::Group(_) => 3,
+            Channel::GuildCategory(_) => 4,
+            Channel::GuildNews(_) => 5,
+            Channel::GuildStore(_) => 6,
+            Channel::Unknown { kind, .. } => *kind,
+        }
+    }
 
-    #[serde(rename = "3")]
-    Group,
+    /// The ID of the guild to which the channel belongs, if any.
+    pub fn guild_id(&self) -> Option<GuildId> {
+        match self {
+            Channel::GuildText(channel) => Some(channel.guild_data.guild_id()),
+            Channel::GuildVoice(channel) => Some(channel.guild_data.guild_id()),
+            Channel::GuildCategory(channel) => Some(channel.guild_data.guild_id()),
+            Channel::Direct(channel)
+            | Channel::Group(channel)
+            | Channel::GuildNews(channel)
+            | Channel::GuildStore(channel)
+            | Channel::Unknown { channel, .. } => channel.guild_id(),
+        }
+    }
 
-    #[serde(rename = "4")]
-    GuildCategory(GuildCategoryChannel),
+    /// Sets the ID of the guild, for channels which were sent without it (such
+    /// as those in GUILD_CREATE).
+    pub(crate) fn set_guild_id(&mut self, guild_id: Snowflake) {
+        match self {
+            Channel::GuildText(channel) => channel.guild_data.guild_id = guild_id,
+            Channel::GuildVoice(channel) => channel.guild_data.guild_id = guild_id,
+            Channel::GuildCategory(channel) => channel.guild_data.guild_id = guild_id,
+            Channel::GuildNews(channel)
+            | Channel::GuildStore(channel)
+            | Channel::Unknown { channel, .. } => channel.guild_id = Some(guild_id),
+            Channel::Direct(_) | Channel::Group(_) => {}
+        }
+    }
+
+    /// Sets the time at which a message was most recently pinned, for
+    /// channels which keep track of it.
+    pub(crate) fn set_last_pin_at(&mut self, last_pin_at: Option<DateTime<Utc>>) {
+        match self {
+            Channel::GuildText(channel) => channel.text_data.last_pin_at = last_pin_at,
+            Channel::GuildVoice(channel) => channel.text_data.last_pin_at = last_pin_at,
+            _ => {}
+        }
+    }
+}
 
-    #[serde(rename = "5")]
-    GuildNews,
+impl ResourceId for Channel {
+    type Id = ChannelId;
 
-    #[serde(rename = "6")]
-    GuildStore,
+    fn id(&self) -> &Self::Id {
+        match self {
+            Channel::GuildText(channel) => &channel.id,
+            Channel::GuildVoice(channel) => &channel.id,
+            Channel::GuildCategory(channel) => &channel.id,
+            Channel::Direct(channel)
+            | Channel::Group(channel)
+            | Channel::GuildNews(channel)
+            | Channel::GuildStore(channel)
+            | Channel::Unknown { channel, .. } => &channel.id,
+        }
+    }
+}
+
+impl Resource for Channel {
+    fn received_at(&self) -> DateTime<Utc> {
+        match self {
+            Channel::GuildText(channel) => channel.received_at,
+            Channel::GuildVoice(channel) => channel.received_at,
+            Channel::GuildCategory(channel) => channel.received_at,
+            Channel::Direct(channel)
+            | Channel::Group(channel)
+            | Channel::GuildNews(channel)
+            | Channel::GuildStore(channel)
+            | Channel::Unknown { channel, .. } => channel.received_at,
+        }
+    }
 }
 
-create_id!(pub GuildTextChannelId {});
+impl Serialize for Channel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct Tagged<'a, T> {
+            #[serde(rename = "type")]
+            kind: u8,
+
+            #[serde(flatten)]
+            channel: &'a T,
+        }
+
+        let kind = self.kind();
 
-impl From<GuildTextChannelId> for TextChannelId {
-    fn from(this: GuildTextChannelId) -> Self {
-        Self {
-            id: this.id,
+        match self {
+            Channel::GuildText(channel) => Tagged { kind, channel }.serialize(serializer),
+            Channel::GuildVoice(channel) => Tagged { kind, channel }.serialize(serializer),
+            Channel::GuildCategory(channel) => Tagged { kind, channel }.serialize(serializer),
+            Channel::Direct(channel)
+            | Channel::Group(channel)
+            | Channel::GuildNews(channel)
+            | Channel::GuildStore(channel)
+            | Channel::Unknown { channel, .. } => Tagged { kind, channel }.serialize(serializer),
         }
     }
 }
 
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        RawChannel::deserialize(deserializer)?.into_channel()
+    }
+}
+
+/// The fields of every supported channel type, so that channels can be
+/// decoded in a single pass regardless of where `type` appears, without
+/// buffering the other fields until it is known.
+#[derive(Deserialize)]
+struct RawChannel {
+    id: Snowflake,
+
+    #[serde(rename = "type")]
+    kind: u64,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    received_at: DateTime<Utc>,
+
+    #[serde(default)]
+    guild_id: Option<Snowflake>,
+
+    #[serde(default)]
+    position: Option<i16>,
+
+    #[serde(default)]
+    name: Option<String>,
+
+    #[serde(default)]
+    nsfw: bool,
+
+    #[serde(default)]
+    last_message_id: Option<Snowflake>,
+
+    #[serde(default)]
+    last_pin_timestamp: Option<DateTime<Utc>>,
+}
+
+impl RawChannel {
+    fn into_channel<E: de::Error>(self) -> Result<Channel, E> {
+        let id = ChannelId { id: self.id };
+        let received_at = self.received_at;
+
+        let guild_data = || -> Result<GuildChannelData, E> {
+            Ok(GuildChannelData {
+                guild_id: self.guild_id.unwrap_or_else(unknown_guild_id),
+                position: self
+                    .position
+                    .ok_or_else(|| de::Error::missing_field("position"))?,
+                name: self
+                    .name
+                    .clone()
+                    .ok_or_else(|| de::Error::missing_field("name"))?,
+                is_nsfw: self.nsfw,
+            })
+        };
+        let text_data = || TextChannelData::new(self.last_message_id, self.last_pin_timestamp);
+        let partial = || PartialChannel {
+            id,
+            received_at,
+            guild_id: self.guild_id,
+        };
+
+        let channel = match self.kind {
+            0 => Channel::GuildText(GuildTextChannel {
+                id,
+                received_at,
+                guild_data: guild_data()?,
+                text_data: text_data(),
+            }),
+            1 => Channel::Direct(partial()),
+            2 => Channel::GuildVoice(GuildVoiceChannel {
+                id,
+                received_at,
+                guild_data: guild_data()?,
+                text_data: text_data(),
+            }),
+            3 => Channel::Group(partial()),
+            4 => Channel::GuildCategory(GuildCategoryChannel {
+                id,
+                received_at,
+                guild_data: guild_data()?,
+            }),
+            5 => Channel::GuildNews(partial()),
+            6 => Channel::GuildStore(partial()),
+            // New channel types are added regularly, and shouldn't prevent the event
+            // from being received.
+            kind if kind <= u8::MAX as u64 => Channel::Unknown {
+                kind: kind as u8,
+                channel: partial(),
+            },
+            kind => {
+                return Err(de::Error::invalid_value(
+                    Unexpected::Unsigned(kind),
+                    &"a channel type that fits in a u8",
+                ))
+            }
+        };
+
+        Ok(channel)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct GuildTextChannel {
     #[serde(flatten)]
-    id: GuildTextChannelId,
+    id: ChannelId,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    pub(crate) received_at: DateTime<Utc>,
 
     #[serde(flatten)]
     guild_data: GuildChannelData,
@@ -56,14 +266,14 @@ pub struct GuildTextChannel {
     text_data: TextChannelData,
 }
 
-
-create_id!(pub GuildVoiceChannelId {});
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct GuildVoiceChannel {
     #[serde(flatten)]
-    id: GuildVoiceChannelId,
+    id: ChannelId,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    pub(crate) received_at: DateTime<Utc>,
 
     #[serde(flatten)]
     guild_data: GuildChannelData,
@@ -72,30 +282,64 @@ pub struct GuildVoiceChannel {
     text_data: TextChannelData,
 }
 
-create_id!(pub GuildCategoryChannelId {});
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct GuildCategoryChannel {
     #[serde(flatten)]
-    id: GuildCategoryChannelId,
+    id: ChannelId,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    pub(crate) received_at: DateTime<Utc>,
 
     #[serde(flatten)]
     guild_data: GuildChannelData,
 }
 
+/// A channel of a type whose details aren't modeled yet.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
-pub struct GuildChannelData {
+pub struct PartialChannel {
     #[serde(flatten)]
-    guild_id: GuildId,
+    id: ChannelId,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    pub(crate) received_at: DateTime<Utc>,
+
+    /// Not sent for private channels.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guild_id: Option<Snowflake>,
+}
+
+impl PartialChannel {
+    pub fn guild_id(&self) -> Option<GuildId> {
+        self.guild_id.map(|id| GuildId { id })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GuildChannelData {
+    // Not sent for channels in GUILD_CREATE, which set it afterwards.
+    #[serde(default = "unknown_guild_id")]
+    guild_id: Snowflake,
     position: i16,
     name: String,
 
-    #[serde(rename = "nsfw")]
+    #[serde(rename = "nsfw", default)]
     is_nsfw: bool,
 }
 
+impl GuildChannelData {
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+}
+
+fn unknown_guild_id() -> Snowflake {
+    Snowflake(0)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct VoiceChannelData {
@@ -8,11 +8,24 @@ create_id!(pub TextChannelId {});
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct TextChannelData {
+    // Sent as a bare ID, without the ID of the channel.
     #[serde(default)]
-    last_message_id: Option<MessageId>,
+    last_message_id: Option<Snowflake>,
 
     #[serde(rename = "last_pin_timestamp", default)]
-    last_pin_at: Option<DateTime<Utc>>,
+    pub(crate) last_pin_at: Option<DateTime<Utc>>,
+}
+
+impl TextChannelData {
+    pub(crate) fn new(
+        last_message_id: Option<Snowflake>,
+        last_pin_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            last_message_id,
+            last_pin_at,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
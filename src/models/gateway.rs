@@ -13,6 +13,10 @@ pub struct Gateway {
 }
 
 impl Gateway {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+
     pub fn route() -> Route {
         Api::route().join("/gateway")
     }
@@ -43,6 +47,17 @@ impl BotGateway {
         Gateway::route().join("/bot")
     }
 
+    pub async fn get(ctx: &Context<'_>) -> Result<Self> {
+        Self::route().get().send(ctx, ()).await
+    }
+
+    /// The gateway to which shards should connect.
+    pub fn gateway(&self) -> Gateway {
+        Gateway {
+            url: self.url.clone(),
+        }
+    }
+
     /// The URL that can be used to connect to the gateway.
     pub fn url(&self) -> &String {
         &self.url
@@ -66,6 +81,7 @@ pub struct SessionStartLimit {
     total: u64,
     remaining: u64,
     reset_after: u64,
+    max_concurrency: u64,
 }
 
 impl SessionStartLimit {
@@ -83,4 +99,9 @@ impl SessionStartLimit {
     pub fn resets_after(&self) -> Duration {
         Duration::from_millis(self.reset_after)
     }
+
+    /// The number of shards that are allowed to identify concurrently.
+    pub fn max_concurrency(&self) -> &u64 {
+        &self.max_concurrency
+    }
 }
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::prelude::*;
+
+// `id` is the ID of the user.
+create_id!(pub MemberId {
+    guild_id: Snowflake,
+});
+
+impl MemberId {
+    pub(crate) fn new(id: Snowflake, guild_id: Snowflake) -> Self {
+        Self { id, guild_id }
+    }
+
+    pub fn guild_id(&self) -> GuildId {
+        GuildId { id: self.guild_id }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        UserId { id: self.id }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(from = "RawMember", into = "RawMember")]
+#[non_exhaustive]
+pub struct Member {
+    id: MemberId,
+    pub(crate) received_at: DateTime<Utc>,
+
+    pub user: User,
+    pub nick: Option<String>,
+    pub roles: Vec<Snowflake>,
+    pub joined_at: DateTime<Utc>,
+    pub premium_since: Option<DateTime<Utc>>,
+    pub is_deafened: bool,
+    pub is_muted: bool,
+}
+
+impl_resource!(Member, MemberId);
+
+impl Member {
+    /// Sets the ID of the guild, for members which were sent without it (such
+    /// as those in member chunks).
+    pub(crate) fn set_guild_id(&mut self, guild_id: Snowflake) {
+        self.id.guild_id = guild_id;
+    }
+}
+
+/// The representation of members sent by the gateway, in which the ID of the
+/// guild is omitted in some cases and the ID of the user is nested.
+#[derive(Deserialize, Serialize)]
+struct RawMember {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guild_id: Option<Snowflake>,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    received_at: DateTime<Utc>,
+
+    user: User,
+
+    #[serde(default)]
+    nick: Option<String>,
+
+    roles: Vec<Snowflake>,
+    joined_at: DateTime<Utc>,
+
+    #[serde(default)]
+    premium_since: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    deaf: bool,
+
+    #[serde(default)]
+    mute: bool,
+}
+
+impl From<RawMember> for Member {
+    fn from(raw: RawMember) -> Self {
+        Self {
+            id: MemberId {
+                id: raw.user.id().id,
+                guild_id: raw.guild_id.unwrap_or(Snowflake(0)),
+            },
+            received_at: raw.received_at,
+            user: raw.user,
+            nick: raw.nick,
+            roles: raw.roles,
+            joined_at: raw.joined_at,
+            premium_since: raw.premium_since,
+            is_deafened: raw.deaf,
+            is_muted: raw.mute,
+        }
+    }
+}
+
+impl From<Member> for RawMember {
+    fn from(member: Member) -> Self {
+        Self {
+            guild_id: Some(member.id.guild_id),
+            received_at: member.received_at,
+            user: member.user,
+            nick: member.nick,
+            roles: member.roles,
+            joined_at: member.joined_at,
+            premium_since: member.premium_since,
+            deaf: member.is_deafened,
+            mute: member.is_muted,
+        }
+    }
+}
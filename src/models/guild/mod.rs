@@ -1,3 +1,5 @@
+pub mod member;
+
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr as DeserializeRepr, Serialize_repr as SerializeRepr};
 
@@ -5,6 +7,13 @@ use crate::models::prelude::*;
 
 create_id!(pub GuildId {});
 
+impl GuildId {
+    /// The ID of the shard which receives events for this guild.
+    pub fn shard_id(&self, shard_count: u64) -> u64 {
+        (self.id.0 >> 22) % shard_count.max(1)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[non_exhaustive]
 pub struct UnavailableGuild {
@@ -27,7 +36,11 @@ pub struct Guild {
     pub(crate) received_at: DateTime<Utc>,
 
     pub name: String,
-    pub region: String,
+
+    /// Not sent since gateway v10, in which voice regions are set per channel.
+    #[serde(default)]
+    pub region: Option<String>,
+
     pub preferred_locale: String,
     pub verification_level: VerificationLevel,
     pub default_message_notifications: MessageNotificationLevel,
@@ -110,6 +123,7 @@ pub struct PartialGuildRole {
     pub color: u32,
     pub position: u64,
     // TODO: Permissions bitfield struct
+    #[serde(with = "super::stringified")]
     pub permissions: u64,
 
     #[serde(rename = "hoist")]
@@ -246,3 +260,51 @@ pub enum GuildFeature {
     PublicDisabled,
     WelcomeScreenEnabled,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::gateway::test_support;
+
+    fn guild(version: u8) -> Value {
+        let mut guild = test_support::guild_create(81384788765712384, "Guild", Vec::new());
+
+        // Permissions are sent as integers in v6 and as strings since v8.
+        let permissions = match version {
+            6 => json!(104324673),
+            _ => json!("104324673"),
+        };
+        guild["roles"] = json!([{
+            "id": "81384788765712384",
+            "name": "@everyone",
+            "color": 0,
+            "position": 0,
+            "permissions": permissions,
+            "hoist": false,
+            "managed": false,
+            "mentionable": false,
+        }]);
+
+        if version >= 10 {
+            guild.as_object_mut().unwrap().remove("region");
+        }
+
+        guild
+    }
+
+    #[test]
+    fn decodes_guilds_of_every_version() {
+        for &version in &[6, 8, 10] {
+            let guild: Guild = serde_json::from_value(guild(version))
+                .unwrap_or_else(|err| panic!("Failed to decode v{} guild: {}", version, err));
+
+            assert_eq!(guild.roles[0].permissions, 104324673);
+            assert_eq!(
+                guild.region.as_deref(),
+                if version >= 10 { None } else { Some("europe") }
+            );
+        }
+    }
+}
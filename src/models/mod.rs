@@ -6,6 +6,8 @@ use serde::Serialize;
 mod channel;
 mod gateway;
 mod guild;
+mod presence;
+mod user;
 
 mod macros {
     #[macro_export]
@@ -71,7 +73,9 @@ pub(self) mod prelude {
 
 pub use channel::{message::*, text::*, *};
 pub use gateway::*;
-pub use guild::*;
+pub use guild::{member::*, *};
+pub use presence::*;
+pub use user::*;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 #[serde(transparent)]
@@ -93,6 +97,55 @@ pub trait Resource: ResourceId + Clone {
     fn received_at(&self) -> DateTime<Utc>;
 }
 
+/// (De)serializes integers which may exceed the range of JSON numbers as
+/// strings.
+pub(crate) mod stringified {
+    use std::fmt;
+
+    use serde::{
+        de::{self, Deserializer, Visitor},
+        Serializer,
+    };
+
+    pub fn serialize<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct StringifiedVisitor;
+
+        impl<'de> Visitor<'de> for StringifiedVisitor {
+            type Value = u64;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("integer as a number or string")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(value)
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                value.parse().map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(StringifiedVisitor)
+    }
+}
+
 // TODO: Custom Serialize derivation using `Serializer::is_human_readable`
 mod snowflake {
     use std::fmt;
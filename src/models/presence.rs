@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr as DeserializeRepr, Serialize_repr as SerializeRepr};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Activity {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub kind: ActivityKind,
+
+    /// The stream URL, only used for [`ActivityKind::Streaming`].
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Activity {
+    pub fn new(kind: ActivityKind, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            url: None,
+        }
+    }
+
+    pub fn streaming(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: ActivityKind::Streaming,
+            url: Some(url.into()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DeserializeRepr, SerializeRepr)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum ActivityKind {
+    Game = 0,
+    Streaming = 1,
+    Listening = 2,
+    Watching = 3,
+    Custom = 4,
+    Competing = 5,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub enum Status {
+    #[serde(rename = "online")]
+    Online,
+
+    #[serde(rename = "dnd")]
+    DoNotDisturb,
+
+    #[serde(rename = "idle")]
+    Idle,
+
+    #[serde(rename = "invisible")]
+    Invisible,
+
+    #[serde(rename = "offline")]
+    Offline,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::Online
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::prelude::*;
+
+create_id!(pub UserId {});
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct User {
+    #[serde(flatten)]
+    id: UserId,
+
+    #[serde(rename = "_received_at", default = "Utc::now")]
+    pub(crate) received_at: DateTime<Utc>,
+
+    pub username: String,
+    pub discriminator: String,
+
+    #[serde(default)]
+    pub avatar: Option<String>,
+
+    #[serde(rename = "bot", default)]
+    pub is_bot: bool,
+}
+
+impl_resource!(User, UserId);
@@ -56,4 +56,44 @@ where
     async fn remove_one(&self, id: &R::Id) -> Option<R> {
         self._remove(&id)
     }
+
+    async fn remove_where(&self, predicate: &(dyn Fn(&R) -> bool + Send + Sync)) -> Vec<R> {
+        let ids: Vec<R::Id> = self
+            .0
+            .iter()
+            .filter(|entry| predicate(entry.value()))
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        ids.iter().flat_map(|id| self._remove(id)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{ResourceId, UnavailableGuild};
+
+    #[tokio::test]
+    async fn removes_matching_resources() {
+        let store = MemoryStore::new();
+        let guilds: Vec<UnavailableGuild> = serde_json::from_value(json!([
+            { "id": "81384788765712384" },
+            { "id": "81384788765712385" },
+        ]))
+        .unwrap();
+        store.insert(&guilds).await;
+
+        let kept = *guilds[1].id();
+        let removed = store
+            .remove_where(&|guild: &UnavailableGuild| *guild.id() != kept)
+            .await;
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id(), guilds[0].id());
+        assert!(store.get_one(guilds[0].id()).await.is_none());
+        assert!(store.get_one(&kept).await.is_some());
+    }
 }
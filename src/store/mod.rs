@@ -15,6 +15,10 @@ where
     async fn insert(&self, resources: &[R]) -> Vec<R>;
     async fn remove(&self, ids: &[R::Id]) -> Vec<R>;
 
+    /// Removes every resource matching `predicate`, e.g. those belonging to a
+    /// guild which was left.
+    async fn remove_where(&self, predicate: &(dyn Fn(&R) -> bool + Send + Sync)) -> Vec<R>;
+
     async fn get_one(&self, id: &R::Id) -> Option<R> {
         self.get(&[id.clone()]).await.into_iter().next()
     }
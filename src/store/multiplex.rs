@@ -66,4 +66,19 @@ where
             .find_map(|resource| resource)
             .await
     }
+
+    // Resources are evicted from every store, since they would otherwise never be
+    // removed from the others.
+    async fn remove_where(&self, predicate: &(dyn Fn(&R) -> bool + Send + Sync)) -> Vec<R> {
+        stream::iter(&self.0)
+            .then(|store| store.remove_where(predicate))
+            .fold(Vec::new(), |removed, resources| {
+                if removed.is_empty() {
+                    resources
+                } else {
+                    removed
+                }
+            })
+            .await
+    }
 }